@@ -0,0 +1,156 @@
+use prelude::*;
+use prelude::hash::Hash;
+
+use super::PrefixTree;
+
+/// Collects keys and construction options for a counting tree, applied
+/// together by [`build`](#method.build).
+#[derive(Debug, Clone)]
+pub struct TreeBuilder<K> {
+    keys: Vec<Vec<K>>,
+    sorted_children: bool,
+    capacity: usize,
+    order: Option<HashMap<K, u32>>,
+    max_depth: Option<usize>,
+}
+
+impl<K: Ord + Hash + Clone> TreeBuilder<K> {
+    pub fn new() -> TreeBuilder<K> {
+        TreeBuilder {
+            keys: Vec::new(),
+            sorted_children: false,
+            capacity: 0,
+            order: None,
+            max_depth: None,
+        }
+    }
+
+    /// Whether to order the children of every node by first element once
+    /// the keys are appended, see
+    /// [`PrefixTree::sort_children`](struct.PrefixTree.html#method.sort_children).
+    /// Children otherwise stay in insertion order.
+    pub fn sorted_children(mut self, sorted: bool) -> TreeBuilder<K> {
+        self.sorted_children = sorted;
+        self
+    }
+
+    /// Makes room in the node index for `items` distinct items, see
+    /// [`PrefixTree::reserve`](struct.PrefixTree.html#method.reserve).
+    pub fn capacity(mut self, items: usize) -> TreeBuilder<K> {
+        self.capacity = items;
+        self
+    }
+
+    /// Reorders every key by descending frequency in `order` as it is
+    /// appended, see
+    /// [`PrefixTree::append_sorted`](struct.PrefixTree.html#method.append_sorted).
+    pub fn frequency_order(mut self, order: HashMap<K, u32>) -> TreeBuilder<K> {
+        self.order = Some(order);
+        self
+    }
+
+    /// Cuts every appended key to its first `max_depth` elements, see
+    /// [`PrefixTree::with_max_depth`](struct.PrefixTree.html#method.with_max_depth).
+    /// The cap stays with the tree for later appends.
+    pub fn max_depth(mut self, max_depth: usize) -> TreeBuilder<K> {
+        self.max_depth = Some(max_depth);
+        self
+    }
+
+    /// Adds `key` to the keys appended by [`build`](#method.build).
+    pub fn append<Q: AsRef<[K]>>(mut self, key: Q) -> TreeBuilder<K> {
+        self.keys.push(key.as_ref().to_vec());
+        self
+    }
+
+    /// Adds every key of `keys` to the keys appended by
+    /// [`build`](#method.build).
+    pub fn append_all<I, Q>(mut self, keys: I) -> TreeBuilder<K>
+        where I: IntoIterator<Item = Q>, Q: AsRef<[K]>
+    {
+        self.keys.extend(keys.into_iter().map(|key| key.as_ref().to_vec()));
+        self
+    }
+
+    /// Returns a tree holding the added keys, built with the chosen
+    /// options. Sorting children happens once all keys are in, so keys
+    /// appended to the tree afterwards go after their siblings.
+    pub fn build(self) -> PrefixTree<K, u32> {
+        let mut tree = match self.max_depth {
+            Some(max) => PrefixTree::with_max_depth(max),
+            None => PrefixTree::new(),
+        };
+        tree.reserve(self.capacity);
+        for key in self.keys {
+            match self.order {
+                Some(ref order) => tree.append_sorted(&key, order),
+                None => tree.append(key),
+            }
+        }
+        if self.sorted_children {
+            tree.sort_children();
+        }
+        tree
+    }
+}
+
+impl<K: Ord + Hash + Clone> Default for TreeBuilder<K> {
+    fn default() -> TreeBuilder<K> {
+        TreeBuilder::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TreeBuilder;
+    use prelude::*;
+    use Tree;
+
+    fn transactions() -> Vec<Vec<u32>> {
+        vec![vec![5, 1, 3], vec![2, 5], vec![1, 2, 5], vec![4]]
+    }
+
+    #[test]
+    fn test_sorted_children() {
+        let unsorted = TreeBuilder::new().append_all(transactions()).build();
+        let sorted = TreeBuilder::new().append_all(transactions()).sorted_children(true).build();
+        assert_eq!(sorted, unsorted);
+        assert_ne!(unsorted.iter().collect::<Vec<_>>(), unsorted.iter_sorted().collect::<Vec<_>>());
+        assert_eq!(sorted.iter().collect::<Vec<_>>(), sorted.iter_sorted().collect::<Vec<_>>());
+        assert!(TreeBuilder::<u32>::new().sorted_children(true).build().is_empty());
+    }
+
+    #[test]
+    fn test_frequency_order() {
+        let mut order = HashMap::new();
+        order.insert(5, 3);
+        order.insert(1, 2);
+        order.insert(2, 2);
+        let t = TreeBuilder::new().frequency_order(order).append_all(transactions()).build();
+        assert!(t.contains([5, 1, 3]));
+        assert!(t.contains([5, 2]));
+        assert!(t.contains([5, 1, 2]));
+        assert_eq!(t.find([5]).unwrap().value, Some(3));
+        assert_eq!(t.root.child_count(), 2);
+        assert_eq!(TreeBuilder::new().append_all(transactions()).build().root.child_count(), 4);
+        assert_eq!(t.total_inserts(), 4);
+    }
+
+    #[test]
+    fn test_max_depth() {
+        let mut t = TreeBuilder::new().max_depth(2).append_all(transactions()).build();
+        assert_eq!(t.get_count([5, 1]), Some(1));
+        assert!(!t.contains([5, 1, 3]));
+        t.append(vec![2, 5, 9]);
+        assert_eq!(t.get_count([2, 5]), Some(2));
+    }
+
+    #[test]
+    fn test_capacity() {
+        let t = TreeBuilder::new().capacity(100).append(vec![1, 2]).build();
+        assert!(t.nodeindex.capacity() >= 100);
+        let mut expected = Tree::new();
+        expected.append(vec![1, 2]);
+        assert_eq!(t, expected);
+    }
+}
@@ -0,0 +1,90 @@
+//! Loading counting trees from delimited text.
+
+use prelude::*;
+use prelude::hash::Hash;
+use prelude::str::FromStr;
+
+use super::PrefixTree;
+
+/// A field of delimited text which is not a valid item, see
+/// [`PrefixTree::append_csv`](struct.PrefixTree.html#method.append_csv).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    row: usize,
+    field: String,
+}
+
+impl ParseError {
+    /// Returns the number of the row holding the field, counting from 1.
+    pub fn row(&self) -> usize {
+        self.row
+    }
+
+    /// Returns the field which did not parse.
+    pub fn field(&self) -> &str {
+        &self.field
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "row {}: invalid item {:?}", self.row, self.field)
+    }
+}
+
+#[cfg(not(feature = "no_std"))]
+impl ::std::error::Error for ParseError {}
+
+impl<K: Eq + Hash + Clone + FromStr> PrefixTree<K, u32> {
+    /// Appends every line of `data` as a transaction of items separated
+    /// by `delimiter`, returning how many were appended. Fields are
+    /// trimmed and empty ones ignored, so lines without items are skipped.
+    ///
+    /// A field which does not parse fails naming its row, keeping the
+    /// transactions appended before it.
+    pub fn append_csv(&mut self, data: &str, delimiter: char) -> Result<u64, ParseError> {
+        let mut n = 0;
+        for (i, line) in data.lines().enumerate() {
+            let items = line.split(delimiter)
+                .map(str::trim)
+                .filter(|field| !field.is_empty())
+                .map(|field| field.parse().map_err(|_| ParseError { row: i + 1, field: field.to_owned() }))
+                .collect::<Result<Vec<K>, _>>()?;
+            if !items.is_empty() {
+                self.append(items);
+                n += 1;
+            }
+        }
+        Ok(n)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ParseError;
+    use prelude::*;
+    use Tree;
+
+    #[test]
+    fn test_append_csv() {
+        let mut t = Tree::new();
+        let data = "8,5,1,3\n6, 2 ,4,7\r\n\n8,,6,2,5,4,1,\n,,\n8;2";
+        assert_eq!(t.append_csv(data, ','), Err(ParseError { row: 6, field: "8;2".to_owned() }));
+        assert_eq!(t.total_inserts(), 3);
+        assert!(t.contains([6, 2, 4, 7]));
+        assert!(t.contains([8, 6, 2, 5, 4, 1]));
+
+        let mut t = Tree::new();
+        assert_eq!(t.append_csv("8;5;1;3\n8;2\n", ';'), Ok(2));
+        assert_eq!(t.find([8]).unwrap().value, Some(2));
+        assert_eq!(t.append_csv("", ','), Ok(0));
+    }
+
+    #[test]
+    fn test_parse_error() {
+        let err = Tree::new().append_csv("1,2\n3,-4", ',').unwrap_err();
+        assert_eq!(err.row(), 2);
+        assert_eq!(err.field(), "-4");
+        assert_eq!(err.to_string(), "row 2: invalid item \"-4\"");
+    }
+}
@@ -0,0 +1,207 @@
+use prelude::*;
+use prelude::hash::Hash;
+use prelude::ops::{Deref, DerefMut};
+
+use super::PrefixTree;
+
+/// A view into a single key of a counting tree, returned by
+/// [`PrefixTree::entry`](struct.PrefixTree.html#method.entry).
+///
+/// The count of an entry is how often its key was appended itself, the
+/// appends of longer keys passing through it left out. Changing it
+/// updates the prefix counts stored along the key's path.
+pub enum Entry<'a, K: 'a> {
+    /// The key was appended before.
+    Occupied(OccupiedEntry<'a, K>),
+    /// The key is not in the tree, though it may be a prefix of one.
+    Vacant(VacantEntry<'a, K>),
+}
+
+pub struct OccupiedEntry<'a, K: 'a> {
+    tree: &'a mut PrefixTree<K, u32>,
+    key: Vec<K>,
+    count: u32,
+}
+
+pub struct VacantEntry<'a, K: 'a> {
+    tree: &'a mut PrefixTree<K, u32>,
+    key: Vec<K>,
+}
+
+impl<'a, K: Eq + Hash + Clone> Entry<'a, K> {
+    pub(crate) fn new(tree: &'a mut PrefixTree<K, u32>, key: Vec<K>) -> Entry<'a, K> {
+        match tree.find(&key).filter(|n| n.is_terminal()).map(|n| n.own_count()) {
+            Some(count) => Entry::Occupied(OccupiedEntry { tree, key, count }),
+            None => Entry::Vacant(VacantEntry { tree, key }),
+        }
+    }
+
+    pub fn key(&self) -> &[K] {
+        match *self {
+            Entry::Occupied(ref e) => e.key(),
+            Entry::Vacant(ref e) => e.key(),
+        }
+    }
+
+    /// Applies `f` to the count of an occupied entry.
+    pub fn and_modify<F: FnOnce(&mut u32)>(self, f: F) -> Entry<'a, K> {
+        match self {
+            Entry::Occupied(mut e) => {
+                let mut count = e.get();
+                f(&mut count);
+                e.insert(count);
+                Entry::Occupied(e)
+            },
+            e => e,
+        }
+    }
+
+    /// Appends the key `default` times if it is vacant, returning its
+    /// count.
+    pub fn or_insert(self, default: u32) -> u32 {
+        self.or_insert_with(|| default)
+    }
+
+    /// Appends the key as often as `default` returns if it is vacant,
+    /// returning its count.
+    pub fn or_insert_with<F: FnOnce() -> u32>(self, default: F) -> u32 {
+        match self {
+            Entry::Occupied(e) => e.get(),
+            Entry::Vacant(e) => e.insert(default()),
+        }
+    }
+}
+
+impl<'a, K: Eq + Hash + Clone> OccupiedEntry<'a, K> {
+    pub fn key(&self) -> &[K] {
+        &self.key
+    }
+
+    pub fn get(&self) -> u32 {
+        self.count
+    }
+
+    /// Sets the count of the key, returning the previous one. A count of
+    /// zero removes the key.
+    pub fn insert(&mut self, count: u32) -> u32 {
+        if count > self.count {
+            self.tree.append_with_count(&self.key, count - self.count);
+        } else if count < self.count {
+            self.tree.remove(&self.key);
+            self.tree.append_with_count(&self.key, count);
+        }
+        let old = self.count;
+        self.count = count;
+        old
+    }
+
+    /// Removes the key, returning its count.
+    pub fn remove(self) -> u32 {
+        self.tree.remove(&self.key);
+        self.count
+    }
+}
+
+/// The count of a stored key, returned by
+/// [`PrefixTree::get_mut`](struct.PrefixTree.html#method.get_mut).
+///
+/// Like an [`Entry`](enum.Entry.html) it holds how often the key was
+/// appended itself. Changes are written back when it is dropped, fixing
+/// the counts along the key's path and the total.
+pub struct CountMut<'a, K: 'a + Eq + Hash + Clone> {
+    entry: OccupiedEntry<'a, K>,
+    count: u32,
+}
+
+impl<'a, K: Eq + Hash + Clone> CountMut<'a, K> {
+    pub(crate) fn new(entry: OccupiedEntry<'a, K>) -> CountMut<'a, K> {
+        let count = entry.get();
+        CountMut { entry, count }
+    }
+}
+
+impl<'a, K: Eq + Hash + Clone> Deref for CountMut<'a, K> {
+    type Target = u32;
+
+    fn deref(&self) -> &u32 {
+        &self.count
+    }
+}
+
+impl<'a, K: Eq + Hash + Clone> DerefMut for CountMut<'a, K> {
+    fn deref_mut(&mut self) -> &mut u32 {
+        &mut self.count
+    }
+}
+
+impl<'a, K: Eq + Hash + Clone> Drop for CountMut<'a, K> {
+    fn drop(&mut self) {
+        self.entry.insert(self.count);
+    }
+}
+
+impl<'a, K: Eq + Hash + Clone> VacantEntry<'a, K> {
+    pub fn key(&self) -> &[K] {
+        &self.key
+    }
+
+    /// Appends the key `count` times, returning `count`.
+    pub fn insert(self, count: u32) -> u32 {
+        self.tree.append_with_count(&self.key, count);
+        count
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Entry;
+    use Tree;
+
+    #[test]
+    fn test_entry() {
+        let mut t = Tree::new();
+        t.append(vec![3, 137]);
+        t.append(vec![3, 137, 2]);
+
+        assert_eq!(t.entry(vec![3, 137]).and_modify(|v| *v += 10).or_insert(1), 11);
+        assert_eq!(t.get_count([3, 137]), Some(12));
+        assert_eq!(t.get_count([3, 137, 2]), Some(1));
+        assert_eq!(t.total_inserts(), 12);
+
+        // a prefix of a stored key is still vacant
+        match t.entry(vec![3]) {
+            Entry::Vacant(e) => assert_eq!(e.key(), &[3]),
+            Entry::Occupied(_) => panic!("[3] was never appended"),
+        }
+        assert_eq!(t.entry(vec![3]).and_modify(|v| *v += 10).or_insert(4), 4);
+        assert_eq!(t.get_count([3]), Some(16));
+        assert_eq!(t.entry(vec![3]).or_insert(100), 4);
+        assert_eq!(t.validate(), Ok(()));
+    }
+
+    #[test]
+    fn test_entry_lower_count() {
+        let mut t = Tree::new();
+        t.append_with_count(vec![1, 2], 5);
+        t.append(vec![1, 2, 3]);
+
+        if let Entry::Occupied(mut e) = t.entry(vec![1, 2]) {
+            assert_eq!(e.get(), 5);
+            assert_eq!(e.insert(2), 5);
+            assert_eq!(e.get(), 2);
+        }
+        assert_eq!(t.get_count([1, 2]), Some(3));
+        assert_eq!(t.get_count([1, 2, 3]), Some(1));
+        assert_eq!(t.total_inserts(), 3);
+
+        t.entry(vec![1, 2]).and_modify(|v| *v = 0);
+        assert!(!t.contains([1, 2]));
+        assert_eq!(t.get_count([1, 2, 3]), Some(1));
+
+        if let Entry::Occupied(e) = t.entry(vec![1, 2, 3]) {
+            assert_eq!(e.remove(), 1);
+        }
+        assert!(t.is_empty());
+        assert_eq!(t.validate(), Ok(()));
+    }
+}
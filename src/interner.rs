@@ -0,0 +1,113 @@
+use prelude::*;
+use prelude::convert::TryFrom;
+
+/// Maps item names to the `u32` ids a [`Tree`](type.Tree.html) counts.
+///
+/// Ids are handed out in order of first appearance, starting at 0. The
+/// interner is kept apart from any tree, so several trees can share one
+/// and their ids stay comparable.
+#[derive(Debug, Clone, Default)]
+pub struct Interner {
+    ids: HashMap<String, u32>,
+    names: Vec<String>,
+}
+
+impl Interner {
+    pub fn new() -> Interner {
+        Interner::default()
+    }
+
+    /// Returns the id of `name`, giving it the next free id if it was not
+    /// interned before.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `name` is new and every `u32` id is already taken.
+    pub fn intern(&mut self, name: &str) -> u32 {
+        if let Some(&id) = self.ids.get(name) {
+            return id;
+        }
+        let id = u32::try_from(self.names.len()).expect("interner ran out of u32 ids");
+        self.ids.insert(name.to_owned(), id);
+        self.names.push(name.to_owned());
+        id
+    }
+
+    /// Returns the id `name` was interned as, without interning it.
+    pub fn id(&self, name: &str) -> Option<u32> {
+        self.ids.get(name).cloned()
+    }
+
+    /// Returns the name interned as `id`.
+    pub fn resolve(&self, id: u32) -> Option<&str> {
+        self.names.get(id as usize).map(|s| s.as_str())
+    }
+
+    /// Interns every name of a transaction, returning the key to append.
+    pub fn intern_all<I, S>(&mut self, names: I) -> Vec<u32>
+        where I: IntoIterator<Item = S>, S: AsRef<str>
+    {
+        names.into_iter().map(|name| self.intern(name.as_ref())).collect()
+    }
+
+    /// Returns the ids of a transaction's names, or `None` if any of them
+    /// was never interned, in which case no key holding it was appended.
+    pub fn ids<I, S>(&self, names: I) -> Option<Vec<u32>>
+        where I: IntoIterator<Item = S>, S: AsRef<str>
+    {
+        names.into_iter().map(|name| self.id(name.as_ref())).collect()
+    }
+
+    /// Returns the names of the ids in `key`, or `None` if any id was not
+    /// handed out by this interner.
+    pub fn resolve_all(&self, key: &[u32]) -> Option<Vec<&str>> {
+        key.iter().map(|&id| self.resolve(id)).collect()
+    }
+
+    /// Returns the number of interned names.
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use prelude::*;
+    use super::Interner;
+    use Tree;
+
+    #[test]
+    fn test_interner() {
+        let mut names = Interner::new();
+        assert_eq!(names.intern("bread"), 0);
+        assert_eq!(names.intern("milk"), 1);
+        assert_eq!(names.intern("bread"), 0);
+        assert_eq!(names.len(), 2);
+        assert_eq!(names.resolve(1), Some("milk"));
+        assert_eq!(names.resolve(2), None);
+        assert_eq!(names.id("eggs"), None);
+    }
+
+    #[test]
+    fn test_shared_between_trees() {
+        let mut names = Interner::new();
+        let mut t = Tree::new();
+        let mut u = Tree::new();
+        t.append(names.intern_all(vec!["bread", "milk"]));
+        t.append(names.intern_all(["bread", "milk", "eggs"]));
+        u.append(names.intern_all(vec!["eggs".to_string()]));
+
+        let key = names.ids(["bread", "milk"]).unwrap();
+        assert_eq!(t.get_count(&key), Some(2));
+        assert_eq!(u.get_count(names.ids(["eggs"]).unwrap()), Some(1));
+        assert_eq!(names.ids(["bread", "butter"]), None);
+
+        let keys: Vec<_> = t.iter_sorted().map(|(key, _)| names.resolve_all(&key).unwrap().join(" ")).collect();
+        assert_eq!(keys, vec!["bread milk", "bread milk eggs"]);
+        assert_eq!(names.resolve_all(&[0, 7]), None);
+    }
+}
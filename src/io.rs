@@ -0,0 +1,181 @@
+//! Loading counting trees from readers and storing them to writers, not
+//! available with the `no_std` feature.
+
+use std::hash::Hash;
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Write};
+use std::str::FromStr;
+
+use super::{PrefixTree, Tree};
+
+impl<K: Eq + Hash + Clone + FromStr> PrefixTree<K, u32> {
+    /// Appends every line of `reader` as a transaction of whitespace
+    /// separated items, returning how many were appended. Blank lines are
+    /// skipped.
+    ///
+    /// An item which does not parse fails with `InvalidData` naming its
+    /// line, keeping the transactions appended before it.
+    pub fn append_from_reader<R: BufRead>(&mut self, reader: R) -> io::Result<u64> {
+        let mut n = 0;
+        for (i, line) in reader.lines().enumerate() {
+            let line = line?;
+            let items = line.split_whitespace()
+                .map(|item| item.parse().map_err(|_| {
+                    io::Error::new(io::ErrorKind::InvalidData, format!("line {}: invalid item {:?}", i + 1, item))
+                }))
+                .collect::<io::Result<Vec<K>>>()?;
+            if !items.is_empty() {
+                self.append(items);
+                n += 1;
+            }
+        }
+        Ok(n)
+    }
+}
+
+impl Tree {
+    /// Writes the appended keys with how often each was appended itself,
+    /// so that [`read_binary`](#method.read_binary) rebuilds the tree.
+    ///
+    /// Every key is stored as its length, its items and its count, each a
+    /// LEB128 varint. Nothing of the node layout is stored.
+    pub fn write_binary<W: Write>(&self, w: W) -> io::Result<()> {
+        let mut w = BufWriter::new(w);
+        for (key, n) in self.own_counts() {
+            write_varint(&mut w, key.len() as u64)?;
+            for item in key {
+                write_varint(&mut w, u64::from(item))?;
+            }
+            write_varint(&mut w, u64::from(n))?;
+        }
+        w.flush()
+    }
+
+    /// Reads a tree written by [`write_binary`](#method.write_binary),
+    /// failing with `InvalidData` on malformed input and `UnexpectedEof`
+    /// when it stops in the middle of a key.
+    pub fn read_binary<R: Read>(r: R) -> io::Result<Tree> {
+        let mut r = BufReader::new(r);
+        let mut keys = Vec::new();
+        while let Some(len) = read_varint(&mut r)? {
+            let mut key = Vec::new();
+            for _ in 0..len {
+                key.push(read_u32(&mut r)?);
+            }
+            keys.push((key, read_u32(&mut r)?));
+        }
+        Ok(Tree::from_own_counts(keys))
+    }
+}
+
+fn write_varint<W: Write>(w: &mut W, mut x: u64) -> io::Result<()> {
+    while x >= 0x80 {
+        w.write_all(&[x as u8 | 0x80])?;
+        x >>= 7;
+    }
+    w.write_all(&[x as u8])
+}
+
+/// Reads a varint, returning `None` if `r` ends before it starts.
+fn read_varint<R: Read>(r: &mut R) -> io::Result<Option<u64>> {
+    let mut x = 0;
+    for shift in (0..64).step_by(7) {
+        let mut byte = [0];
+        if r.read(&mut byte)? == 0 {
+            return if shift == 0 { Ok(None) } else { Err(io::ErrorKind::UnexpectedEof.into()) };
+        }
+        x |= u64::from(byte[0] & 0x7f) << shift;
+        if byte[0] & 0x80 == 0 {
+            return Ok(Some(x));
+        }
+    }
+    Err(io::Error::new(io::ErrorKind::InvalidData, "varint is too long"))
+}
+
+fn read_u32<R: Read>(r: &mut R) -> io::Result<u32> {
+    match read_varint(r)? {
+        Some(x) if x <= u64::from(u32::MAX) => Ok(x as u32),
+        Some(x) => Err(io::Error::new(io::ErrorKind::InvalidData, format!("{} does not fit in 32 bits", x))),
+        None => Err(io::ErrorKind::UnexpectedEof.into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Cursor, ErrorKind};
+
+    use Tree;
+
+    #[test]
+    fn test_append_from_reader() {
+        let mut t = Tree::new();
+        let input = "8 5 1 3\n6 2 4 7\n\n  8\t6 2 5 4 1  \n   \n8 2";
+        assert_eq!(t.append_from_reader(Cursor::new(input)).unwrap(), 4);
+        assert_eq!(t.total_inserts(), 4);
+        assert!(t.contains([8, 6, 2, 5, 4, 1]));
+        assert!(t.contains([8, 2]));
+        assert_eq!(t.get_count([8]), None);
+        assert_eq!(t.find([8]).unwrap().value, Some(3));
+    }
+
+    #[test]
+    fn test_append_from_reader_invalid() {
+        let mut t = Tree::new();
+        let err = t.append_from_reader(Cursor::new("1 2\n\n3 x 4\n5\n")).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+        assert_eq!(err.to_string(), "line 3: invalid item \"x\"");
+        assert_eq!(t.total_inserts(), 1);
+        assert!(t.append_from_reader(Cursor::new("-1")).is_err());
+    }
+
+    #[test]
+    fn test_binary_round_trip() {
+        let mut t: Tree = vec![
+            vec![8, 5, 1, 3], vec![6, 2, 4, 7], vec![8, 6, 2, 5, 4, 1],
+            vec![2, 8, 4, 0, 7], vec![8, 6, 2, 0], vec![6, 8, 4, 1],
+            vec![8, 5, 0], vec![8, 6, 5, 0, 3], vec![8, 2], vec![1, 7],
+        ].into_iter().collect();
+        t.append_with_count([8, 5], 300);
+        t.append([u32::MAX]);
+        t.append([]);
+        let mut bytes = Vec::new();
+        t.write_binary(&mut bytes).unwrap();
+        let u = Tree::read_binary(&bytes[..]).unwrap();
+        assert_eq!(u.iter_sorted().collect::<Vec<_>>(), t.iter_sorted().collect::<Vec<_>>());
+        assert_eq!(u, t);
+        assert_eq!(u.total_inserts(), t.total_inserts());
+
+        // keys whose own appends were all taken off are kept
+        t.decrement([8, 2]);
+        let mut bytes = Vec::new();
+        t.write_binary(&mut bytes).unwrap();
+        let u = Tree::read_binary(&bytes[..]).unwrap();
+        assert_eq!(u.get_count([8, 2]), Some(0));
+        assert_eq!(u, t);
+
+        let mut bytes = Vec::new();
+        Tree::new().write_binary(&mut bytes).unwrap();
+        assert!(bytes.is_empty());
+        assert!(Tree::read_binary(&bytes[..]).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_binary_layout() {
+        let mut t = Tree::new();
+        t.append_with_count([3, 137], 2);
+        let mut bytes = Vec::new();
+        t.write_binary(&mut bytes).unwrap();
+        assert_eq!(bytes, vec![2, 3, 0x89, 0x01, 2]);
+    }
+
+    #[test]
+    fn test_read_binary_invalid() {
+        let err = Tree::read_binary(&[2, 3, 0x89][..]).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::UnexpectedEof);
+        let err = Tree::read_binary(&[2, 3][..]).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::UnexpectedEof);
+        let err = Tree::read_binary(&[1, 0x80, 0x80, 0x80, 0x80, 0x10, 1][..]).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+        let err = Tree::read_binary(&[0xff; 11][..]).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+    }
+}
@@ -1,334 +1,3711 @@
-use std::rc::Rc;
-use std::collections::{HashMap, HashSet};
+#![cfg_attr(feature = "no_std", no_std)]
 
-#[derive(Debug)]
+#[cfg(feature = "no_std")]
+#[cfg_attr(not(test), macro_use)]
+extern crate alloc;
+#[cfg(feature = "no_std")]
+extern crate hashbrown;
+#[cfg(all(feature = "no_std", test))]
+#[macro_use]
+extern crate std;
+#[cfg(feature = "serde")]
+extern crate serde;
+
+use prelude::*;
+use prelude::fmt::{self, Write};
+use prelude::cmp::Reverse;
+use prelude::iter::FromIterator;
+use prelude::hash::{Hash, Hasher};
+
+mod prelude;
+mod builder;
+mod csv;
+mod entry;
+mod interner;
+mod mining;
+mod string_tree;
+#[cfg(not(feature = "no_std"))]
+mod io;
+#[cfg(feature = "serde")]
+mod serde_impl;
+
+pub use builder::TreeBuilder;
+pub use csv::ParseError;
+pub use entry::{CountMut, Entry, OccupiedEntry, VacantEntry};
+pub use interner::Interner;
+pub use mining::{Rule, TransactionDatabase};
+pub use string_tree::{StringTree, Tokenizer};
+
+#[derive(Debug, Clone)]
 #[derive(PartialEq, Eq, Hash)]
-pub struct Node<T> {
-    key: Vec<u32>,
+pub struct Node<K, T> {
+    key: Vec<K>,
     pub value: Option<T>,
-    child: Option<Rc<Node<T>>>,
-    sibling: Option<Rc<Node<T>>>,
-    next: Option<Rc<Node<T>>>,
-    tree: *mut Tree,
+    terminal: bool,
+    children: Branches<K, T>,
+}
+
+impl<K, T> Drop for Node<K, T> {
+    /// Dismantles the subtree through a work list, as dropping the
+    /// children in turn would recurse once per level and overflow the
+    /// stack on deep trees. Children still shared, by the node index or a
+    /// clone of the tree, are left to whoever drops them last.
+    fn drop(&mut self) {
+        let mut stack = mem::take(&mut self.children.nodes);
+        while let Some(node) = stack.pop() {
+            if let Ok(mut node) = Arc::try_unwrap(node) {
+                stack.append(&mut node.children.nodes);
+            }
+        }
+    }
+}
+
+/// The children of a node in the order they were added, looked up by the
+/// first element of their key, which no two of them share.
+#[derive(Clone)]
+struct Branches<K, T> {
+    nodes: Vec<Arc<Node<K, T>>>,
+    positions: HashMap<K, usize>,
+}
+
+impl<K: Eq + Hash, T> Branches<K, T> {
+    fn new() -> Branches<K, T> {
+        Branches { nodes: Vec::new(), positions: HashMap::new() }
+    }
+
+    fn get(&self, first: &K) -> Option<&Arc<Node<K, T>>> {
+        self.positions.get(first).map(|&i| &self.nodes[i])
+    }
+
+    fn get_mut(&mut self, first: &K) -> Option<&mut Arc<Node<K, T>>> {
+        let nodes = &mut self.nodes;
+        self.positions.get(first).map(move |&i| &mut nodes[i])
+    }
+
+    fn push(&mut self, node: Arc<Node<K, T>>) where K: Clone {
+        self.positions.insert(node.key[0].clone(), self.nodes.len());
+        self.nodes.push(node);
+    }
+
+    /// Removes the child starting with `first`, keeping the others in
+    /// order.
+    fn remove(&mut self, first: &K) -> Option<Arc<Node<K, T>>> {
+        let i = self.positions.remove(first)?;
+        for position in self.positions.values_mut() {
+            if *position > i {
+                *position -= 1;
+            }
+        }
+        Some(self.nodes.remove(i))
+    }
+
+    /// Keeps only the children for which `f` returns true, in order.
+    fn retain<F: FnMut(&Arc<Node<K, T>>) -> bool>(&mut self, f: F) where K: Clone {
+        self.nodes.retain(f);
+        self.reposition();
+    }
+
+    /// Orders the children by the first element of their key.
+    fn sort(&mut self) where K: Ord + Clone {
+        self.nodes.sort_by(|a, b| a.key[0].cmp(&b.key[0]));
+        self.reposition();
+    }
+
+    fn reposition(&mut self) where K: Clone {
+        self.positions.clear();
+        for (i, node) in self.nodes.iter().enumerate() {
+            self.positions.insert(node.key[0].clone(), i);
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+}
+
+impl<K: fmt::Debug, T: fmt::Debug> fmt::Debug for Branches<K, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_list().entries(&self.nodes).finish()
+    }
+}
+
+impl<K: PartialEq, T: PartialEq> PartialEq for Branches<K, T> {
+    fn eq(&self, other: &Branches<K, T>) -> bool {
+        self.nodes == other.nodes
+    }
+}
+
+impl<K: Eq, T: Eq> Eq for Branches<K, T> {}
+
+impl<K: Hash, T: Hash> Hash for Branches<K, T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.nodes.hash(state)
+    }
+}
+
+impl<K: Eq + Hash, T> Node<K, T> {
+    pub fn new<I: Into<Vec<K>>>(key: I, value: T) -> Node<K, T> {
+        Node {
+            key: key.into(),
+            value: Some(value),
+            terminal: true,
+            children: Branches::new(),
+        }
+    }
+
+    /// Returns the empty node at the top of a tree, holding the
+    /// top-level nodes as its children.
+    fn root() -> Node<K, T> {
+        Node {
+            key: Vec::new(),
+            value: None,
+            terminal: false,
+            children: Branches::new(),
+        }
+    }
+
+    /// Returns whether an appended key ends at this node, as opposed to
+    /// the node only existing because two appended keys diverge below it.
+    pub fn is_terminal(&self) -> bool {
+        self.terminal
+    }
+
+    /// Returns the part of the key stored at this node, following the
+    /// keys of the nodes above it.
+    pub fn key(&self) -> &[K] {
+        &self.key
+    }
+
+    /// Returns whether this node has no children.
+    pub fn is_leaf(&self) -> bool {
+        self.children.is_empty()
+    }
+
+    /// Returns the number of direct children of this node.
+    pub fn child_count(&self) -> usize {
+        self.children.nodes.len()
+    }
+
+    fn common_prefix<Q: AsRef<[K]>>(&self, other: Q) -> usize {
+        common_prefix_len(&self.key, other.as_ref())
+    }
+
+    /// Returns the node `key` ends at, `key` starting with this node's own
+    /// key. Children are looked up by their first element, so every level
+    /// takes constant time, and the search runs in a loop so deep keys do
+    /// not grow the stack.
+    pub fn find<Q: AsRef<[K]>>(&self, key: Q) -> Option<&Node<K, T>> {
+        let mut key = key.as_ref();
+        let mut node = self;
+        loop {
+            let prefix = node.common_prefix(key);
+            if prefix < node.key.len() {
+                return None;
+            }
+            key = &key[prefix..];
+            match key.first() {
+                Some(first) => node = node.children.get(first)?,
+                None => return Some(node),
+            }
+        }
+    }
+
+    /// Returns an iterator over the direct children of this node.
+    pub fn children(&self) -> Children<'_, K, T> {
+        Children { iter: self.children.nodes.iter() }
+    }
+
+    /// Returns a copy of this node and everything below it sharing no
+    /// nodes with the original.
+    fn deep_clone(&self) -> Node<K, T> where K: Clone, T: Clone {
+        let mut children = Branches::new();
+        for child in self.children() {
+            children.push(Arc::new(child.deep_clone()));
+        }
+        Node {
+            key: self.key.clone(),
+            value: self.value.clone(),
+            terminal: self.terminal,
+            children,
+        }
+    }
+
+    /// Returns a copy of this node and everything below it with every
+    /// value passed through `f`.
+    fn map_values<W, F: Fn(T) -> W>(&self, f: &F) -> Node<K, W> where K: Clone, T: Clone {
+        let mut children = Branches::new();
+        for child in self.children() {
+            children.push(Arc::new(child.map_values(f)));
+        }
+        Node {
+            key: self.key.clone(),
+            value: self.value.clone().map(f),
+            terminal: self.terminal,
+            children,
+        }
+    }
+
+    /// Returns the smallest key ending in this node's subtree, this node
+    /// included, appending to `key` the elements from this node down.
+    fn first_key(&self, mut key: Vec<K>) -> Option<Vec<K>> where K: Ord + Clone {
+        let mut node = self;
+        key.extend_from_slice(&node.key);
+        while !node.terminal {
+            node = node.children().min_by(|a, b| a.key[0].cmp(&b.key[0]))?;
+            key.extend_from_slice(&node.key);
+        }
+        Some(key)
+    }
+
+    /// Returns the largest key ending in this node's subtree, as
+    /// [`first_key`](#method.first_key) does the smallest.
+    fn last_key(&self, mut key: Vec<K>) -> Option<Vec<K>> where K: Ord + Clone {
+        let mut node = self;
+        key.extend_from_slice(&node.key);
+        while let Some(child) = node.children().max_by(|a, b| a.key[0].cmp(&b.key[0])) {
+            key.extend_from_slice(&child.key);
+            node = child;
+        }
+        if node.terminal { Some(key) } else { None }
+    }
+
+    /// Finds the node below this one at which `key` ends, possibly in the
+    /// middle of its own key, pushing the keys of the nodes between them
+    /// onto `path`. An empty `key` ends at this node.
+    fn find_prefix(&self, key: &[K], path: &mut Vec<K>) -> Option<&Node<K, T>> where K: Clone {
+        let mut key = key;
+        let mut node = self;
+        while let Some(first) = key.first() {
+            let child = node.children.get(first)?;
+            let prefix = child.common_prefix(key);
+            if prefix == key.len() {
+                return Some(child);
+            } else if prefix < child.key.len() {
+                return None;
+            }
+            path.extend_from_slice(&child.key);
+            key = &key[prefix..];
+            node = child;
+        }
+        Some(node)
+    }
+
+    /// Pushes the full keys of all terminals in this node's subtree onto
+    /// `out`, `path` holding the keys of the nodes above it.
+    fn collect_keys(&self, path: &mut Vec<K>, out: &mut Vec<Vec<K>>) where K: Clone {
+        let len = path.len();
+        path.extend_from_slice(&self.key);
+        if self.terminal {
+            out.push(path.clone());
+        }
+        for child in self.children() {
+            child.collect_keys(path, out);
+        }
+        path.truncate(len);
+    }
+
+    /// Pushes every terminal in this node's subtree within `max_dist` edits
+    /// of `query` onto `out`. `row` is the last row of the edit distance
+    /// matrix between `query` and `path`, the keys of the nodes above.
+    fn collect_within(&self, query: &[K], max_dist: usize, row: &[usize], path: &mut Vec<K>,
+                      out: &mut Vec<(Vec<K>, T)>)
+        where K: Clone, T: Clone
+    {
+        let len = path.len();
+        let mut row = row.to_vec();
+        for k in &self.key {
+            path.push(k.clone());
+            let mut next = Vec::with_capacity(row.len());
+            next.push(row[0] + 1);
+            for (j, q) in query.iter().enumerate() {
+                let cost = if q == k { 0 } else { 1 };
+                next.push((next[j] + 1).min(row[j + 1] + 1).min(row[j] + cost));
+            }
+            row = next;
+            if row.iter().all(|&d| d > max_dist) {
+                path.truncate(len);
+                return;
+            }
+        }
+        if self.terminal && row[query.len()] <= max_dist {
+            if let Some(ref value) = self.value {
+                out.push((path.clone(), value.clone()));
+            }
+        }
+        for child in self.children() {
+            child.collect_within(query, max_dist, &row, path, out);
+        }
+        path.truncate(len);
+    }
+
+    /// Pushes every terminal in this node's subtree matching `pattern`
+    /// element by element onto `out`, `None` matching any element.
+    fn collect_matching(&self, pattern: &[Option<K>], path: &mut Vec<K>, out: &mut Vec<(Vec<K>, T)>)
+        where K: Clone, T: Clone
+    {
+        if self.key.len() > pattern.len() ||
+            !self.key.iter().zip(pattern).all(|(k, p)| p.as_ref().is_none_or(|p| p == k)) {
+            return;
+        }
+        let len = path.len();
+        path.extend_from_slice(&self.key);
+        let rest = &pattern[self.key.len()..];
+        if rest.is_empty() {
+            if let (true, Some(value)) = (self.terminal, self.value.as_ref()) {
+                out.push((path.clone(), value.clone()));
+            }
+        } else {
+            for child in self.children() {
+                child.collect_matching(rest, path, out);
+            }
+        }
+        path.truncate(len);
+    }
+
+    /// Calls `f` on this node and every node below it, depth first, with
+    /// `path` holding the keys of the nodes above it.
+    fn walk<F: FnMut(&[K], &Node<K, T>)>(&self, path: &mut Vec<K>, f: &mut F) where K: Clone {
+        let len = path.len();
+        path.extend_from_slice(&self.key);
+        f(path, self);
+        for child in self.children() {
+            child.walk(path, f);
+        }
+        path.truncate(len);
+    }
+
+    /// Writes `nodes`, one per line, with their subtrees indented below
+    /// them.
+    fn fmt_branches(nodes: &[Arc<Node<K, T>>], f: &mut fmt::Formatter, indent: &mut String) -> fmt::Result
+        where K: fmt::Debug, T: fmt::Display
+    {
+        for (i, node) in nodes.iter().enumerate() {
+            let last = i + 1 == nodes.len();
+            write!(f, "{}{}{:?}", indent, if last { "└─ " } else { "├─ " }, node.key)?;
+            if let Some(ref value) = node.value {
+                write!(f, " ({})", value)?;
+            }
+            writeln!(f)?;
+            let len = indent.len();
+            indent.push_str(if last { "   " } else { "│  " });
+            Self::fmt_branches(&node.children.nodes, f, indent)?;
+            indent.truncate(len);
+        }
+        Ok(())
+    }
+
+    /// Writes this node and its subtree as Graphviz statements. Returns
+    /// the id of this node.
+    fn dot_node(&self, out: &mut String, ids: &mut usize) -> usize
+        where K: fmt::Debug, T: fmt::Display
+    {
+        let id = *ids;
+        *ids += 1;
+        let mut label = format!("{:?}", self.key);
+        if let Some(ref value) = self.value {
+            let _ = write!(label, " ({})", value);
+        }
+        let _ = writeln!(out, "    n{} [label=\"{}\"];", id, label.replace('\\', "\\\\").replace('"', "\\\""));
+        for child in self.children() {
+            let child_id = child.dot_node(out, ids);
+            let _ = writeln!(out, "    n{} -> n{};", id, child_id);
+        }
+        id
+    }
+
+    fn count_key_len(&self) -> (usize, usize) {
+        let mut no_nodes = 1;
+        let mut no_key_el = self.key.len();
+
+        for child in self.children() {
+            let c = child.count_key_len();
+            no_nodes += c.0;
+            no_key_el += c.1;
+        }
+
+        (no_nodes, no_key_el)
+    }
+
+    /// Returns the number of nodes on the longest path starting at this
+    /// node.
+    fn height(&self) -> usize {
+        1 + self.children().map(|x| x.height()).max().unwrap_or(0)
+    }
+
+    /// Returns the number of terminals in this node's subtree, this node
+    /// included.
+    fn count_terminals(&self) -> usize {
+        self.terminal as usize + self.children().map(|x| x.count_terminals()).sum::<usize>()
+    }
+
+    fn count_nodes(&self, seen: &mut HashSet<*const Node<K, T>>) -> usize {
+        if !seen.insert(self) {
+            return 0;
+        }
+        1 + self.children().map(|x| x.count_nodes(seen)).sum::<usize>()
+    }
+}
+
+/// Iterator over the direct children of a node, in the order they were
+/// added.
+pub struct Children<'a, K: 'a, T: 'a> {
+    iter: slice::Iter<'a, Arc<Node<K, T>>>,
+}
+
+impl<'a, K, T> Iterator for Children<'a, K, T> {
+    type Item = &'a Node<K, T>;
+
+    fn next(&mut self) -> Option<&'a Node<K, T>> {
+        self.iter.next().map(|x| &**x)
+    }
+}
+
+enum AppendType {
+    SameNode,
+    SplitNode,
+    NewStraightChild,
+    NewGayChild,
+}
+
+/// A value accumulated along the path of every appended key.
+///
+/// Integers saturate at their maximum instead of overflowing, so a hot key
+/// clamps rather than panicking in debug builds or wrapping in release.
+pub trait Weight: Clone {
+    /// Returns `self` with `weight` added.
+    fn accumulate(self, weight: Self) -> Self;
+}
+
+macro_rules! saturating_weight {
+    ($($t:ty)*) => ($(
+        impl Weight for $t {
+            fn accumulate(self, weight: $t) -> $t {
+                self.saturating_add(weight)
+            }
+        }
+    )*)
+}
+
+saturating_weight!(u8 u16 u32 u64 usize i8 i16 i32 i64 isize);
+
+impl Weight for f32 {
+    fn accumulate(self, weight: f32) -> f32 {
+        self + weight
+    }
+}
+
+impl Weight for f64 {
+    fn accumulate(self, weight: f64) -> f64 {
+        self + weight
+    }
+}
+
+/// A weight with a unit, which [`append`](struct.PrefixTree.html#method.append)
+/// adds for every key.
+pub trait Count: Weight {
+    /// Returns the weight of a single append.
+    fn one() -> Self;
+}
+
+macro_rules! unit_count {
+    ($($t:ty)*) => ($(
+        impl Count for $t {
+            fn one() -> $t {
+                1
+            }
+        }
+    )*)
+}
+
+unit_count!(u8 u16 u32 u64 usize i8 i16 i32 i64 isize);
+
+impl Count for f32 {
+    fn one() -> f32 {
+        1.0
+    }
+}
+
+impl Count for f64 {
+    fn one() -> f64 {
+        1.0
+    }
+}
+
+impl<K: Eq + Hash + Clone, T: Clone> Node<K, T> {
+    /// Changes the node in `slot` in place through `f`.
+    ///
+    /// The node is taken out of `index` while it changes, so that `slot`
+    /// holds the only reference to it and `Arc::make_mut` does not copy
+    /// it. Its entries are only emptied, not dropped, so putting it back
+    /// does not allocate either.
+    fn update_at<F>(slot: &mut Arc<Node<K, T>>, index: &mut NodeIndex<K, T>, f: F)
+        where F: FnOnce(&mut Node<K, T>, &mut NodeIndex<K, T>)
+    {
+        detach_node(index, slot);
+        f(Arc::make_mut(slot), index);
+        index_node(index, slot);
+    }
+
+    /// Merges every node below this one which holds no key of its own and
+    /// has a single child into that child.
+    fn compact(&mut self, index: &mut NodeIndex<K, T>) {
+        for slot in &mut self.children.nodes {
+            Self::update_at(slot, index, |x, index| {
+                x.compact(index);
+                if x.terminal || x.children.nodes.len() != 1 {
+                    return;
+                }
+                let child = mem::replace(&mut x.children, Branches::new()).nodes.pop().unwrap();
+                unindex_node(index, &child);
+                x.key.extend_from_slice(&child.key);
+                x.terminal = child.terminal;
+                // The value of `x` stays, as it counts every append through
+                // the merged path. The child's children move up unless it
+                // is still shared.
+                match Arc::try_unwrap(child) {
+                    Ok(mut child) => x.children = mem::replace(&mut child.children, Branches::new()),
+                    Err(child) => x.children = child.children.clone(),
+                }
+            });
+        }
+    }
+
+    fn sort_children(&mut self, index: &mut NodeIndex<K, T>) where K: Ord {
+        self.children.sort();
+        for slot in &mut self.children.nodes {
+            Self::update_at(slot, index, |x, index| x.sort_children(index));
+        }
+    }
+}
+
+impl<K: Eq + Hash + Clone, T: Weight> Node<K, T> {
+    fn boxed<I: Into<Vec<K>>>(key: I, value: T, index: &mut NodeIndex<K, T>) -> Arc<Node<K, T>> {
+        let n = Arc::new(Self::new(key, value));
+        index_node(index, &n);
+        n
+    }
+
+    /// Appends `key`, which starts with at least the first element of this
+    /// node's key, adding `weight` to every node on the way. Only nodes
+    /// created by a split or a new branch are allocated; the others are
+    /// changed in place.
+    fn append(&mut self, key: &[K], weight: T, index: &mut NodeIndex<K, T>) {
+        let prefix = self.common_prefix(key);
+        let state;
+        if prefix < key.len() {
+            if prefix < self.key.len() {
+                state = AppendType::NewGayChild;
+            } else {
+                state = AppendType::NewStraightChild;
+            }
+        } else if prefix < self.key.len() {
+            state = AppendType::SplitNode;
+        } else {
+            state = AppendType::SameNode;
+        }
+
+        if let AppendType::NewGayChild | AppendType::SplitNode = state {
+            let tail = Arc::new(Node {
+                key: self.key.split_off(prefix),
+                value: self.value.clone(),
+                terminal: self.terminal,
+                children: mem::replace(&mut self.children, Branches::new()),
+            });
+            index_node(index, &tail);
+            self.children.push(tail);
+        }
+
+        self.value = Some(match self.value.take() {
+            Some(value) => value.accumulate(weight.clone()),
+            None => weight.clone(),
+        });
+        self.terminal = match state {
+            AppendType::SameNode | AppendType::SplitNode => true,
+            AppendType::NewGayChild => false,
+            AppendType::NewStraightChild => self.terminal,
+        };
+        if prefix < key.len() {
+            let rest = &key[prefix..];
+            match self.children.get_mut(&rest[0]) {
+                Some(child) => Self::update_at(child, index, |x, index| x.append(rest, weight, index)),
+                None => self.children.push(Self::boxed(rest, weight, index)),
+            }
+        }
+    }
+}
+
+impl<K: Eq + Hash + Clone> Node<K, u32> {
+    /// Returns how often this node's own key was appended, leaving out
+    /// the appends of longer keys passing through it.
+    fn own_count(&self) -> u32 {
+        let below = self.children().filter_map(|x| x.value).fold(0, u32::saturating_add);
+        self.value.unwrap_or(0).saturating_sub(below)
+    }
+
+    /// Takes `weight` off every node on the path of the stored `key`,
+    /// which starts with this node's key. Children dropping to zero are
+    /// pruned; this node is left for the caller to prune.
+    fn remove(&mut self, key: &[K], weight: u32, index: &mut NodeIndex<K, u32>) {
+        let prefix = self.key.len();
+        if prefix == key.len() {
+            self.terminal = false;
+        } else {
+            let rest = &key[prefix..];
+            if let Some(child) = self.children.get_mut(&rest[0]) {
+                Self::update_at(child, index, |x, index| x.remove(rest, weight, index));
+                if child.value == Some(0) && child.children.is_empty() {
+                    let child = self.children.remove(&rest[0]).unwrap();
+                    unindex_node(index, &child);
+                }
+            }
+        }
+        self.value = self.value.map(|x| x.saturating_sub(weight));
+    }
+
+    /// Drops the subtree of the keys starting with `prefix`, which starts
+    /// with this node's key and is longer than it, returning how many
+    /// keys and appends it held.
+    fn remove_prefix(&mut self, prefix: &[K], index: &mut NodeIndex<K, u32>) -> (usize, u32) {
+        let rest = &prefix[self.key.len()..];
+        let removed = match self.children.get_mut(&rest[0]) {
+            Some(child) => {
+                let common = child.common_prefix(rest);
+                if common == rest.len() {
+                    // The prefix ends in or at the end of the child's key.
+                    let child = self.children.remove(&rest[0]).unwrap();
+                    unindex_subtree(index, &child);
+                    (child.count_terminals(), child.value.unwrap_or(0))
+                } else if common == child.key.len() {
+                    let mut removed = (0, 0);
+                    Self::update_at(child, index, |x, index| removed = x.remove_prefix(rest, index));
+                    if child.value == Some(0) && child.children.is_empty() {
+                        let child = self.children.remove(&rest[0]).unwrap();
+                        unindex_node(index, &child);
+                    }
+                    removed
+                } else {
+                    (0, 0)
+                }
+            },
+            None => (0, 0),
+        };
+        self.value = self.value.map(|x| x.saturating_sub(removed.1));
+        removed
+    }
+
+    /// Drops the children counting less than `min_count` along with
+    /// everything below them, returning how many appends they held. Those
+    /// appends are taken off the value of this node, and children left
+    /// holding no key are dropped as well.
+    fn prune_below(&mut self, min_count: u32, index: &mut NodeIndex<K, u32>) -> u64 {
+        let mut pruned = 0;
+        self.children.retain(|child| {
+            let count = child.value.unwrap_or(0);
+            if count >= min_count {
+                return true;
+            }
+            pruned += u64::from(count);
+            unindex_subtree(index, child);
+            false
+        });
+        for slot in &mut self.children.nodes {
+            Self::update_at(slot, index, |x, index| pruned += x.prune_below(min_count, index));
+        }
+        self.children.retain(|child| {
+            if child.terminal || !child.children.is_empty() {
+                return true;
+            }
+            unindex_node(index, child);
+            false
+        });
+        let pruned_count = cmp::min(pruned, u64::from(u32::MAX)) as u32;
+        self.value = self.value.map(|x| x.saturating_sub(pruned_count));
+        pruned
+    }
+
+    /// Multiplies the value of this node and every node below it by
+    /// `factor`, rounding down.
+    fn decay(&mut self, factor: f64, index: &mut NodeIndex<K, u32>) {
+        self.value = self.value.map(|x| (f64::from(x) * factor) as u32);
+        for slot in &mut self.children.nodes {
+            Self::update_at(slot, index, |x, index| x.decay(factor, index));
+        }
+    }
+
+    fn scale(&mut self, factor: u32, index: &mut NodeIndex<K, u32>) {
+        self.value = self.value.map(|x| x.saturating_mul(factor));
+        for slot in &mut self.children.nodes {
+            Self::update_at(slot, index, |x, index| x.scale(factor, index));
+        }
+    }
+
+    /// Takes one off every node on the path of the stored `key`, which
+    /// starts with this node's key, saturating at zero.
+    fn decrement(&mut self, key: &[K], index: &mut NodeIndex<K, u32>) {
+        let prefix = self.key.len();
+        if prefix < key.len() {
+            let rest = &key[prefix..];
+            if let Some(child) = self.children.get_mut(&rest[0]) {
+                Self::update_at(child, index, |x, index| x.decrement(rest, index));
+            }
+        }
+        self.value = self.value.map(|x| x.saturating_sub(1));
+    }
+}
+
+/// Iterator over the appended keys of a tree and their counts.
+///
+/// Keys are visited depth first: a node comes before its children, which
+/// come before the node's next sibling.
+pub struct Iter<'a, K: 'a, V: 'a> {
+    /// Nodes still to visit, with the length of the key above them and
+    /// their depth.
+    stack: Vec<(&'a Node<K, V>, usize, usize)>,
+    path: Vec<K>,
+    max_depth: usize,
+}
+
+impl<'a, K: Clone, V: Clone> Iterator for Iter<'a, K, V> {
+    type Item = (Vec<K>, V);
+
+    fn next(&mut self) -> Option<(Vec<K>, V)> {
+        while let Some((node, len, depth)) = self.stack.pop() {
+            self.path.truncate(len);
+            self.path.extend_from_slice(&node.key);
+            let len = self.path.len();
+            if depth < self.max_depth {
+                self.stack.extend(node.children.nodes.iter().rev().map(|x| (&**x, len, depth + 1)));
+            }
+            if node.terminal {
+                if let Some(ref value) = node.value {
+                    return Some((self.path.clone(), value.clone()));
+                }
+            }
+        }
+        None
+    }
+}
+
+/// Iterator over the appended keys of a tree and their counts in ascending
+/// order, see [`PrefixTree::iter_sorted`](struct.PrefixTree.html#method.iter_sorted)
+/// and [`PrefixTree::range`](struct.PrefixTree.html#method.range).
+pub struct SortedIter<'a, K: 'a, V: 'a> {
+    stack: Vec<(&'a Node<K, V>, usize)>,
+    path: Vec<K>,
+    start: Vec<K>,
+    end: Option<Vec<K>>,
+}
+
+impl<'a, K: Ord, V> SortedIter<'a, K, V> {
+    /// Pushes the children of `node` so that the one with the smallest
+    /// first element is popped first.
+    fn push_children(&mut self, node: &'a Node<K, V>, len: usize) {
+        let start = self.stack.len();
+        self.stack.extend(node.children.nodes.iter().map(|x| (&**x, len)));
+        self.stack[start..].sort_by(|a, b| b.0.key[0].cmp(&a.0.key[0]));
+    }
+}
+
+impl<'a, K: Ord + Clone, V: Clone> Iterator for SortedIter<'a, K, V> {
+    type Item = (Vec<K>, V);
+
+    fn next(&mut self) -> Option<(Vec<K>, V)> {
+        while let Some((node, len)) = self.stack.pop() {
+            self.path.truncate(len);
+            self.path.extend_from_slice(&node.key);
+            // Nodes are reached in ascending order of their keys, so the
+            // first one past the end ends the iteration.
+            if self.end.as_ref().is_some_and(|end| self.path >= *end) {
+                self.stack.clear();
+                return None;
+            }
+            let before = self.path < self.start;
+            if before && !self.start.starts_with(&self.path) {
+                continue;
+            }
+            let len = self.path.len();
+            self.push_children(node, len);
+            if node.terminal && !before {
+                if let Some(ref value) = node.value {
+                    return Some((self.path.clone(), value.clone()));
+                }
+            }
+        }
+        None
+    }
 }
 
-impl<T> Node<T> {
-    pub fn new<K: Into<Vec<u32>>>(key: K, value: T, tree: *mut Tree) -> Node<T> {
-        Node {
-            key: key.into(),
-            value: Some(value),
-            child: None,
-            sibling: None,
-            next: None,
-            tree: tree,
+/// Owning iterator over the appended keys of a tree and their counts, in
+/// the same order as [`Iter`](struct.Iter.html).
+pub struct IntoIter<K, V> {
+    stack: Vec<(Arc<Node<K, V>>, usize)>,
+    path: Vec<K>,
+}
+
+impl<K: Clone, V: Clone> Iterator for IntoIter<K, V> {
+    type Item = (Vec<K>, V);
+
+    fn next(&mut self) -> Option<(Vec<K>, V)> {
+        while let Some((node, len)) = self.stack.pop() {
+            self.path.truncate(len);
+            self.path.extend_from_slice(&node.key);
+            let terminal = node.terminal;
+            // Nodes still shared with someone else are copied from.
+            let (value, children) = match Arc::try_unwrap(node) {
+                Ok(mut node) => (node.value.take(), mem::take(&mut node.children.nodes)),
+                Err(node) => (node.value.clone(), node.children.nodes.clone()),
+            };
+            let len = self.path.len();
+            self.stack.extend(children.into_iter().rev().map(|x| (x, len)));
+            if let (true, Some(value)) = (terminal, value) {
+                return Some((self.path.clone(), value));
+            }
+        }
+        None
+    }
+}
+
+/// Breadth-first iterator over the nodes of a tree.
+///
+/// The top-level nodes come first, then all of their children, then all
+/// grandchildren.
+pub struct Bfs<'a, K: 'a, V: 'a> {
+    queue: VecDeque<&'a Node<K, V>>,
+}
+
+impl<'a, K, V> Iterator for Bfs<'a, K, V> {
+    type Item = &'a Node<K, V>;
+
+    fn next(&mut self) -> Option<&'a Node<K, V>> {
+        let node = self.queue.pop_front()?;
+        self.queue.extend(node.children.nodes.iter().map(|x| &**x));
+        Some(node)
+    }
+}
+
+/// A compressed prefix tree over keys made of `K` elements, storing a
+/// value of type `V` at each node.
+///
+/// Appending a key adds a weight to the value of every node along its
+/// path. [`Tree`] is the counting flavour over `u32` items, where every
+/// append adds one.
+///
+/// The top-level nodes hang below a root node with an empty key, which
+/// also holds the empty key when it is appended.
+#[derive(Debug)]
+pub struct PrefixTree<K, V> {
+    root: Node<K, V>,
+    nodeindex: NodeIndex<K, V>,
+    count: u64,
+    /// The number of elements appended keys are cut to, if any.
+    max_depth: Option<usize>,
+}
+
+/// Maps every item to the nodes whose key contains it, each node keyed by
+/// its address.
+type NodeIndex<K, V> = HashMap<K, HashMap<usize, Arc<Node<K, V>>>>;
+
+/// The 64-bit FNV-1a hash, which unlike the hasher of the standard
+/// library is not seeded randomly. The bytes it is fed still come from
+/// the `Hash` implementations of the hashed types, which the standard
+/// library does not promise to keep between releases.
+struct Fnv(u64);
+
+impl Fnv {
+    fn new() -> Fnv {
+        Fnv(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl Hasher for Fnv {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 = (self.0 ^ u64::from(byte)).wrapping_mul(0x100_0000_01b3);
+        }
+    }
+}
+
+/// Returns the length of the longest prefix `a` and `b` share, which is
+/// where the tree splits a node when a key leaves its path.
+pub fn common_prefix_len<K: PartialEq>(a: &[K], b: &[K]) -> usize {
+    a.iter()
+        .zip(b.iter())
+        .take_while(|&(a, b)| a == b)
+        .count()
+}
+
+/// Returns roughly how many bytes `map` allocates: a key, a value and a
+/// control byte for every slot it has room for.
+fn map_bytes<K, V, S>(map: &HashMap<K, V, S>) -> usize {
+    map.capacity() * (mem::size_of::<K>() + mem::size_of::<V>() + 1)
+}
+
+fn node_addr<K, V>(node: &Node<K, V>) -> usize {
+    node as *const Node<K, V> as usize
+}
+
+fn index_node<K: Eq + Hash + Clone, V>(index: &mut NodeIndex<K, V>, node: &Arc<Node<K, V>>) {
+    for k in &node.key {
+        index.entry(k.clone()).or_default().insert(node_addr(node), Arc::clone(node));
+    }
+}
+
+/// Takes `node` out of `index`, keeping the emptied entries so that
+/// putting it back with `index_node` does not allocate.
+fn detach_node<K: Eq + Hash, V>(index: &mut NodeIndex<K, V>, node: &Node<K, V>) {
+    for k in &node.key {
+        if let Some(nodes) = index.get_mut(k) {
+            nodes.remove(&node_addr(node));
+        }
+    }
+}
+
+fn unindex_node<K: Eq + Hash, V>(index: &mut NodeIndex<K, V>, node: &Node<K, V>) {
+    for k in &node.key {
+        if let Some(nodes) = index.get_mut(k) {
+            nodes.remove(&node_addr(node));
+            if nodes.is_empty() {
+                index.remove(k);
+            }
+        }
+    }
+}
+
+/// Takes `node` and everything below it out of `index`.
+fn unindex_subtree<K: Eq + Hash, V>(index: &mut NodeIndex<K, V>, node: &Node<K, V>) {
+    let mut stack = vec![node];
+    while let Some(node) = stack.pop() {
+        unindex_node(index, node);
+        stack.extend(node.children());
+    }
+}
+
+impl<K: Eq + Hash + fmt::Debug, V: fmt::Display> PrefixTree<K, V> {
+    /// Renders the tree as a Graphviz digraph. Nodes are labelled with
+    /// their key and count, with an edge from every node to each child.
+    pub fn to_dot(&self) -> String {
+        if self.root.children.is_empty() {
+            return "digraph {}\n".to_owned();
+        }
+        let mut out = "digraph {\n".to_owned();
+        let mut ids = 0;
+        for node in self.root.children() {
+            node.dot_node(&mut out, &mut ids);
+        }
+        out.push_str("}\n");
+        out
+    }
+}
+
+impl<K: Eq + Hash + Clone + fmt::Debug, V: PartialOrd> PrefixTree<K, V> {
+    /// Checks the invariants of the tree, describing the first violation
+    /// found.
+    ///
+    /// Every node must have a non-empty key and a value, no child may
+    /// count more than its parent, siblings must start with distinct
+    /// elements and be found by them, and the node index must hold exactly
+    /// the reachable nodes, each under every element of its key.
+    pub fn validate(&self) -> Result<(), String> {
+        fn check_children<K: Eq + Hash + fmt::Debug, V>(node: &Node<K, V>, path: &[K]) -> Result<(), String> {
+            let branches = &node.children;
+            for (i, child) in branches.nodes.iter().enumerate() {
+                let first = match child.key.first() {
+                    Some(first) => first,
+                    None => continue,
+                };
+                if branches.positions.get(first) != Some(&i) {
+                    return Err(format!("siblings below {:?} share the prefix {:?}", path, first));
+                }
+            }
+            if branches.positions.len() != branches.nodes.len() {
+                return Err(format!("children below {:?} are out of step with their lookup", path));
+            }
+            Ok(())
+        }
+
+        let mut result = check_children(&self.root, &[]);
+        let mut indexed = HashSet::new();
+        self.walk(|path, node| {
+            if result.is_err() {
+                return;
+            }
+            result = if node.key.is_empty() {
+                Err(format!("node below {:?} has an empty key", path))
+            } else if node.value.is_none() {
+                Err(format!("node {:?} has no value", path))
+            } else if node.children().any(|x| x.value > node.value) {
+                Err(format!("a child of node {:?} counts more than its parent", path))
+            } else if let Some(k) = node.key.iter().find(|k| {
+                !self.nodeindex.get(*k).is_some_and(|x| x.contains_key(&node_addr(node)))
+            }) {
+                Err(format!("node {:?} is missing from the index of {:?}", path, k))
+            } else {
+                check_children(node, path)
+            };
+            for k in &node.key {
+                indexed.insert((k.clone(), node_addr(node)));
+            }
+        });
+        result?;
+        let entries: usize = self.nodeindex.values().map(|x| x.len()).sum();
+        if entries != indexed.len() {
+            return Err(format!("the index holds {} stale entries", entries - indexed.len()));
+        }
+        Ok(())
+    }
+}
+
+impl<K: Eq + Hash + Clone, V> Default for PrefixTree<K, V> {
+    fn default() -> PrefixTree<K, V> {
+        PrefixTree::new()
+    }
+}
+
+/// Draws the tree with one node per line, showing its key and count.
+/// Children are indented below their parent, siblings share an indent.
+impl<K: Eq + Hash + fmt::Debug, V: fmt::Display> fmt::Display for PrefixTree<K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        Node::fmt_branches(&self.root.children.nodes, f, &mut String::new())
+    }
+}
+
+/// Trees are equal when they hold the same keys with the same counts,
+/// however their nodes are split and in whichever order the keys were
+/// appended.
+impl<K: Eq + Hash + Clone, V: Clone + PartialEq> PartialEq for PrefixTree<K, V> {
+    fn eq(&self, other: &PrefixTree<K, V>) -> bool {
+        let counts = self.to_hashmap();
+        counts.len() == other.distinct_keys() &&
+            other.iter().all(|(key, value)| counts.get(&key) == Some(&value))
+    }
+}
+
+impl<K: Eq + Hash + Clone, V: Clone + Eq> Eq for PrefixTree<K, V> {}
+
+impl<K: Eq + Hash + Clone, V: Clone> Clone for PrefixTree<K, V> {
+    /// Copies every node, so that the copy and its node index share
+    /// nothing with the original.
+    fn clone(&self) -> PrefixTree<K, V> {
+        let mut tree = PrefixTree {
+            root: self.root.deep_clone(),
+            nodeindex: HashMap::with_capacity(self.nodeindex.len()),
+            count: self.count,
+            max_depth: self.max_depth,
+        };
+        tree.reindex();
+        tree
+    }
+}
+
+/// A prefix tree counting how many appended `u32` keys pass through each
+/// node.
+pub type Tree = PrefixTree<u32, u32>;
+
+impl<K: Eq + Hash + Clone, V> PrefixTree<K, V> {
+    pub fn new() -> PrefixTree<K, V> {
+        PrefixTree {
+            root: Node::root(),
+            nodeindex: HashMap::new(),
+            count: 0,
+            max_depth: None,
+        }
+    }
+
+    /// Returns an empty tree which cuts every appended key to its first
+    /// `max_depth` elements, so keys differing only beyond them are
+    /// counted as one. Entries cut their keys the same way, while other
+    /// lookups take keys as they are stored.
+    ///
+    /// Serialization and [`write_binary`](#method.write_binary) store the
+    /// keys and their counts only, so trees read back are not capped.
+    pub fn with_max_depth(max_depth: usize) -> PrefixTree<K, V> {
+        PrefixTree { max_depth: Some(max_depth), ..PrefixTree::new() }
+    }
+
+    pub fn index_node(&mut self, node: &Arc<Node<K, V>>) {
+        index_node(&mut self.nodeindex, node);
+    }
+
+    /// Rebuilds the node index from the nodes reachable from the root.
+    fn reindex(&mut self) {
+        self.nodeindex.clear();
+        let mut stack: Vec<Arc<Node<K, V>>> = self.root.children.nodes.clone();
+        while let Some(node) = stack.pop() {
+            stack.extend(node.children.nodes.iter().cloned());
+            self.index_node(&node);
+        }
+    }
+
+    /// Returns the node `key` ends at. The empty key only has a node once
+    /// it was appended.
+    pub fn find<Q: AsRef<[K]>>(&self, key: Q) -> Option<&Node<K, V>> {
+        self.root.find(key).filter(|n| !n.key.is_empty() || n.terminal)
+    }
+
+    /// Returns a shared reference to the node `key` ends at, the same one
+    /// [`find`](#method.find) borrows. The root is not shared, so the empty
+    /// key has no node here.
+    ///
+    /// Nodes are changed in place only while the tree holds the sole
+    /// references to them, so the next append through a node held on to
+    /// copies it and the held node no longer changes.
+    pub fn get_node<Q: AsRef<[K]>>(&self, key: Q) -> Option<Arc<Node<K, V>>> {
+        let mut key = key.as_ref();
+        let mut node = &self.root;
+        loop {
+            let child = node.children.get(key.first()?)?;
+            if !key.starts_with(&child.key) {
+                return None;
+            }
+            key = &key[child.key.len()..];
+            if key.is_empty() {
+                return Some(Arc::clone(child));
+            }
+            node = child;
+        }
+    }
+
+    /// Returns whether `key` was appended to the tree.
+    ///
+    /// A key which is merely a prefix of appended keys is not contained,
+    /// whether it ends in the middle of a node (like `[3]` when only
+    /// `[3, 137]` was appended) or on a node created by splitting two
+    /// diverging keys.
+    pub fn contains<Q: AsRef<[K]>>(&self, key: Q) -> bool {
+        self.find(key).is_some_and(|n| n.terminal && n.value.is_some())
+    }
+
+    /// Returns the count stored at `key`, or `None` unless `key` was
+    /// appended (see [`contains`](#method.contains)).
+    pub fn get_count<Q: AsRef<[K]>>(&self, key: Q) -> Option<V> where V: Clone {
+        self.find(key).filter(|n| n.terminal).and_then(|n| n.value.clone())
+    }
+
+    /// Returns the longest appended key which is a prefix of `key`, along
+    /// with its count.
+    pub fn longest_prefix<Q: AsRef<[K]>>(&self, key: Q) -> Option<(Vec<K>, V)> where V: Clone {
+        let mut key = key.as_ref();
+        let mut path = Vec::new();
+        let mut best = None;
+        let mut node = &self.root;
+        loop {
+            if node.terminal {
+                if let Some(ref value) = node.value {
+                    best = Some((path.clone(), value.clone()));
+                }
+            }
+            let child = match key.first().and_then(|x| node.children.get(x)) {
+                Some(child) => child,
+                None => return best,
+            };
+            if child.common_prefix(key) < child.key.len() {
+                return best;
+            }
+            path.extend_from_slice(&child.key);
+            key = &key[child.key.len()..];
+            node = child;
+        }
+    }
+
+    /// Returns a tree of the same keys and shape with every value passed
+    /// through `f`, sharing no nodes with this one.
+    pub fn map_values<W, F: Fn(V) -> W>(&self, f: F) -> PrefixTree<K, W> where V: Clone {
+        let mut tree = PrefixTree {
+            root: self.root.map_values(&f),
+            nodeindex: HashMap::with_capacity(self.nodeindex.len()),
+            count: self.count,
+            max_depth: self.max_depth,
+        };
+        tree.reindex();
+        tree
+    }
+
+    /// Returns all appended keys starting with `prefix`, which may end in
+    /// the middle of a node. An empty prefix returns every key.
+    pub fn with_prefix<Q: AsRef<[K]>>(&self, prefix: Q) -> Vec<Vec<K>> {
+        let mut path = Vec::new();
+        let mut keys = Vec::new();
+        if let Some(node) = self.root.find_prefix(prefix.as_ref(), &mut path) {
+            node.collect_keys(&mut path, &mut keys);
+        }
+        keys
+    }
+
+    /// Returns every appended key within `max_dist` insertions, deletions
+    /// or substitutions of `key`, along with its count, in depth-first
+    /// order.
+    ///
+    /// Rows of the edit distance matrix are carried down the tree, so
+    /// shared prefixes are matched once and branches are abandoned as soon
+    /// as no row entry is within `max_dist`. A distance of 0 finds `key`
+    /// itself, and an empty `key` finds the keys of at most `max_dist`
+    /// elements.
+    pub fn find_within_distance<Q: AsRef<[K]>>(&self, key: Q, max_dist: u32) -> Vec<(Vec<K>, V)>
+        where V: Clone
+    {
+        let key = key.as_ref();
+        let row: Vec<usize> = (0..=key.len()).collect();
+        let mut out = Vec::new();
+        self.root.collect_within(key, max_dist as usize, &row, &mut Vec::new(), &mut out);
+        out
+    }
+
+    /// Returns every appended key as long as `pattern` and equal to it
+    /// wherever it is `Some`, along with its count, in depth-first order.
+    /// A `None` matches any single element.
+    pub fn find_wildcard<P: AsRef<[Option<K>]>>(&self, pattern: P) -> Vec<(Vec<K>, V)> where V: Clone {
+        let mut out = Vec::new();
+        self.root.collect_matching(pattern.as_ref(), &mut Vec::new(), &mut out);
+        out
+    }
+
+    /// Returns the smallest appended key, comparing keys element by element
+    /// with a key sorting before every longer key it is a prefix of.
+    ///
+    /// Children are kept in insertion order, so all children of every node
+    /// on the way are scanned for the smallest first element.
+    pub fn min_key(&self) -> Option<Vec<K>> where K: Ord {
+        self.root.first_key(Vec::new())
+    }
+
+    /// Returns the largest appended key in the order of
+    /// [`min_key`](#method.min_key).
+    pub fn max_key(&self) -> Option<Vec<K>> where K: Ord {
+        self.root.last_key(Vec::new())
+    }
+
+    /// Returns the smallest appended key greater than `key` in the order
+    /// of [`min_key`](#method.min_key), or `None` if `key` is at least the
+    /// largest. `key` need not have been appended.
+    ///
+    /// The search follows `key` down the tree, remembering the last
+    /// subtree branching off above it, so only the children of the nodes
+    /// on its path and of the successor's path are scanned.
+    pub fn successor<Q: AsRef<[K]>>(&self, key: Q) -> Option<Vec<K>> where K: Ord {
+        let key = key.as_ref();
+        // The subtree holding the successor if it is not below `node`,
+        // along with the length of the key above it.
+        let mut next: Option<(&Node<K, V>, usize)> = None;
+        let mut node = &self.root;
+        let mut len = 0;
+        loop {
+            let rest = &key[len..];
+            let first = match rest.first() {
+                Some(first) => first,
+                None => {
+                    if let Some(child) = node.children().min_by(|a, b| a.key[0].cmp(&b.key[0])) {
+                        next = Some((child, len));
+                    }
+                    break;
+                },
+            };
+            if let Some(child) = node.children().filter(|x| x.key[0] > *first).min_by(|a, b| a.key[0].cmp(&b.key[0])) {
+                next = Some((child, len));
+            }
+            let child = match node.children.get(first) {
+                Some(child) => child,
+                None => break,
+            };
+            let common = child.common_prefix(rest);
+            if common == child.key.len() {
+                len += common;
+                node = child;
+            } else if common == rest.len() || child.key[common] > rest[common] {
+                return child.first_key(key[..len].to_vec());
+            } else {
+                break;
+            }
+        }
+        next.and_then(|(node, len)| node.first_key(key[..len].to_vec()))
+    }
+
+    /// Returns the largest appended key less than `key` in the order of
+    /// [`min_key`](#method.min_key), or `None` if `key` is at most the
+    /// smallest. `key` need not have been appended; see
+    /// [`successor`](#method.successor).
+    pub fn predecessor<Q: AsRef<[K]>>(&self, key: Q) -> Option<Vec<K>> where K: Ord {
+        let key = key.as_ref();
+        // The subtree holding the predecessor if it is not below `node`,
+        // or `None` for a prefix of `key`, along with the length of the
+        // key above it.
+        let mut prev: Option<(Option<&Node<K, V>>, usize)> = None;
+        let mut node = &self.root;
+        let mut len = 0;
+        while let Some(first) = key[len..].first() {
+            let rest = &key[len..];
+            if let Some(child) = node.children().filter(|x| x.key[0] < *first).max_by(|a, b| a.key[0].cmp(&b.key[0])) {
+                prev = Some((Some(child), len));
+            } else if node.terminal {
+                prev = Some((None, len));
+            }
+            let child = match node.children.get(first) {
+                Some(child) => child,
+                None => break,
+            };
+            let common = child.common_prefix(rest);
+            if common == child.key.len() {
+                len += common;
+                node = child;
+            } else if common < rest.len() && child.key[common] < rest[common] {
+                return child.last_key(key[..len].to_vec());
+            } else {
+                break;
+            }
+        }
+        match prev? {
+            (Some(node), len) => node.last_key(key[..len].to_vec()),
+            (None, len) => Some(key[..len].to_vec()),
+        }
+    }
+
+    /// Returns how many appended keys start with `prefix`, counting the
+    /// same keys as [`with_prefix`](#method.with_prefix) without
+    /// collecting them. `prefix` itself counts if it was appended.
+    pub fn prefix_count<Q: AsRef<[K]>>(&self, prefix: Q) -> usize {
+        self.root.find_prefix(prefix.as_ref(), &mut Vec::new()).map_or(0, |x| x.count_terminals())
+    }
+
+    /// Returns an iterator over the appended keys and their counts, in
+    /// depth-first order.
+    pub fn iter(&self) -> Iter<'_, K, V> {
+        self.iter_depth_limited(usize::MAX)
+    }
+
+    /// Returns an iterator over the appended keys ending at most
+    /// `max_depth` nodes below the root, in the order of
+    /// [`iter`](#method.iter).
+    ///
+    /// Depth counts nodes, not elements: a top-level node is at depth 1
+    /// however long its key, and the same key may lie deeper once longer
+    /// keys split it. With `max_depth` 0 only the empty key is returned,
+    /// if it was appended.
+    pub fn iter_depth_limited(&self, max_depth: usize) -> Iter<'_, K, V> {
+        Iter {
+            stack: vec![(&self.root, 0, 0)],
+            path: Vec::new(),
+            max_depth,
+        }
+    }
+
+    /// Returns the keys appended to both this tree and `other`, in the
+    /// order of [`iter`](#method.iter).
+    pub fn intersect_keys<W>(&self, other: &PrefixTree<K, W>) -> Vec<Vec<K>> where V: Clone {
+        self.iter().map(|(key, _)| key).filter(|key| other.contains(key)).collect()
+    }
+
+    /// Returns the keys appended to this tree but not to `other`, in the
+    /// order of [`iter`](#method.iter).
+    pub fn difference_keys<W>(&self, other: &PrefixTree<K, W>) -> Vec<Vec<K>> where V: Clone {
+        self.iter().map(|(key, _)| key).filter(|key| !other.contains(key)).collect()
+    }
+
+    /// Returns every appended key with its count, as [`iter`](#method.iter)
+    /// gives them. Each key is copied in full, so for wide trees with long
+    /// shared prefixes the map takes far more memory than the tree.
+    pub fn to_hashmap(&self) -> HashMap<Vec<K>, V> where V: Clone {
+        self.iter().collect()
+    }
+
+    /// Returns an iterator over the appended keys and their counts in
+    /// ascending order, comparing keys as [`min_key`](#method.min_key)
+    /// does.
+    ///
+    /// Children are kept in insertion order, so the children of each node
+    /// are sorted by first element when it is reached.
+    pub fn iter_sorted(&self) -> SortedIter<'_, K, V> where K: Ord {
+        SortedIter { stack: vec![(&self.root, 0)], path: Vec::new(), start: Vec::new(), end: None }
+    }
+
+    /// Returns an iterator over the appended keys from `start` up to but
+    /// excluding `end` and their counts, in the order of
+    /// [`iter_sorted`](#method.iter_sorted). Nothing is returned unless
+    /// `start` comes before `end`.
+    ///
+    /// Subtrees wholly before `start` are skipped without being entered,
+    /// and the iteration stops at the first node past `end`.
+    pub fn range<Q: AsRef<[K]>>(&self, start: Q, end: Q) -> SortedIter<'_, K, V> where K: Ord + Clone {
+        let (start, end) = (start.as_ref(), end.as_ref());
+        let stack = if start < end { vec![(&self.root, 0)] } else { Vec::new() };
+        SortedIter { stack, path: Vec::new(), start: start.to_vec(), end: Some(end.to_vec()) }
+    }
+
+    /// Merges every node which was not appended and has a single child
+    /// into that child, as removals can leave such nodes behind. The tree
+    /// then has the shape it would have had if the removed keys had never
+    /// been appended.
+    pub fn compact(&mut self) where V: Clone {
+        self.root.compact(&mut self.nodeindex);
+    }
+
+    /// Orders the children of every node by the first element of their
+    /// key, so that [`iter`](#method.iter) and [`walk`](#method.walk) visit
+    /// keys in ascending order, as [`iter_sorted`](#method.iter_sorted)
+    /// does. Children are found by hashing either way, so lookups cost the
+    /// same; keys appended afterwards are added after their siblings.
+    pub fn sort_children(&mut self) where K: Ord, V: Clone {
+        self.root.sort_children(&mut self.nodeindex);
+    }
+
+    /// Returns a hash of the appended keys and their counts, taken in
+    /// ascending order so that it does not depend on the order of appends.
+    /// Equal trees hash equally, and the hash stays the same across runs
+    /// of one build. Keys and counts are hashed through their `Hash`
+    /// implementations, whose output may change with the compiler, so
+    /// fingerprints are not meant to be stored and compared later.
+    pub fn fingerprint(&self) -> u64 where K: Ord, V: Clone + Hash {
+        let mut hasher = Fnv::new();
+        for pair in self.iter_sorted() {
+            pair.hash(&mut hasher);
+        }
+        hasher.finish()
+    }
+
+    /// Calls `f` on every node, depth first, along with the full key
+    /// leading to it including the node's own key.
+    pub fn walk<F: FnMut(&[K], &Node<K, V>)>(&self, mut f: F) {
+        let mut path = Vec::new();
+        for node in self.root.children() {
+            node.walk(&mut path, &mut f);
+        }
+    }
+
+    /// Returns an iterator over all nodes in breadth-first order, see
+    /// [`Bfs`](struct.Bfs.html).
+    pub fn bfs(&self) -> Bfs<'_, K, V> {
+        Bfs { queue: self.root.children().collect() }
+    }
+
+    /// Returns, for every count some node carries, how many nodes carry
+    /// it. Counts include the appends passing through a node, so this shows
+    /// how many nodes a [`prune_below`](#method.prune_below) threshold
+    /// would keep.
+    pub fn count_histogram(&self) -> BTreeMap<V, usize> where V: Ord + Clone {
+        let mut histogram = BTreeMap::new();
+        for value in self.bfs().filter_map(|x| x.value.clone()) {
+            *histogram.entry(value).or_insert(0) += 1;
+        }
+        histogram
+    }
+
+    /// Returns the number of distinct keys appended to the tree.
+    pub fn distinct_keys(&self) -> usize {
+        self.root.count_terminals()
+    }
+
+    /// Returns an iterator over every node whose key contains `item`, in
+    /// no particular order.
+    ///
+    /// This is the header table of an FP-tree. It is served from the node
+    /// index rather than from links between the nodes: a compressed node
+    /// may hold several items, so it would need one link per item, and
+    /// links would have to be rewritten whenever a node they point to is
+    /// replaced.
+    pub fn node_chain<'a>(&'a self, item: &K) -> impl Iterator<Item = &'a Node<K, V>> + 'a {
+        self.nodeindex.get(item).into_iter().flat_map(|nodes| nodes.values().map(|x| &**x))
+    }
+
+    /// Returns the full key leading to `target` including its own key, or
+    /// `None` if it is not a node of this tree.
+    ///
+    /// Nodes do not know their parent, so this searches the tree for the
+    /// node by address. It lets nodes found through
+    /// [`node_chain`](#method.node_chain) be placed in the tree.
+    pub fn full_key(&self, target: &Node<K, V>) -> Option<Vec<K>> {
+        let mut key = None;
+        self.walk(|path, node| {
+            if key.is_none() && node_addr(node) == node_addr(target) {
+                key = Some(path.to_vec());
+            }
+        });
+        key
+    }
+
+    /// Returns the nodes above `target`, from its top-level ancestor down
+    /// to its parent, or `None` if it is not a node of this tree.
+    ///
+    /// This searches the tree and takes time linear in the number of
+    /// nodes, so walking up from a node is quadratic when done one
+    /// [`find_parent`](#method.find_parent) at a time; take the whole path
+    /// from one call instead.
+    ///
+    /// Nodes have no parent links. A `Weak` link in every child would count
+    /// against its parent in `Arc::make_mut`, which then moves the parent
+    /// instead of changing it in place, so each append would reallocate
+    /// its whole path and relink the children of every node on it. The
+    /// ancestors are found by searching the tree instead.
+    pub fn find_ancestors(&self, target: &Node<K, V>) -> Option<Vec<&Node<K, V>>> {
+        let mut path = Vec::new();
+        let mut stack: Vec<(&Node<K, V>, usize)> = self.root.children().map(|x| (x, 0)).collect();
+        while let Some((node, depth)) = stack.pop() {
+            path.truncate(depth);
+            if node_addr(node) == node_addr(target) {
+                return Some(path);
+            }
+            path.push(node);
+            stack.extend(node.children().map(|x| (x, depth + 1)));
+        }
+        None
+    }
+
+    /// Returns the node directly above `target`, searching the tree as
+    /// [`find_ancestors`](#method.find_ancestors) does.
+    pub fn find_parent(&self, target: &Node<K, V>) -> Option<&Node<K, V>> {
+        self.find_ancestors(target)?.pop()
+    }
+
+    /// Returns the keys of the nodes on the path to the node `key` ends
+    /// at, deepest first: the node's own key, then its parent's, up to the
+    /// top-level node. The root's empty key is left out. `None` if `key`
+    /// does not end at a node, see [`find`](#method.find).
+    ///
+    /// Nodes are compressed, so in a tree holding `[3, 137]` and `[3, 137,
+    /// 137]` the path of the latter is `[[137], [3, 137]]`.
+    pub fn path_to_root<Q: AsRef<[K]>>(&self, key: Q) -> Option<Vec<Vec<K>>> {
+        let mut key = key.as_ref();
+        self.find(key)?;
+        let mut segments = Vec::new();
+        let mut node = &self.root;
+        while let Some(child) = key.first().and_then(|first| node.children.get(first)) {
+            segments.push(child.key.clone());
+            key = &key[child.key.len()..];
+            node = child;
+        }
+        segments.reverse();
+        Some(segments)
+    }
+
+    /// Returns the conditional pattern base of `item`: for every node
+    /// containing it, the items on the path from the root up to but
+    /// excluding `item`, along with the node's count. Paths are returned
+    /// in depth-first order; nodes where `item` starts a top-level key
+    /// have an empty path and are skipped.
+    pub fn conditional_pattern_base(&self, item: &K) -> Vec<(Vec<K>, V)> where V: Clone {
+        let mut base = Vec::new();
+        self.walk(|path, node| {
+            if let Some(i) = node.key.iter().position(|k| k == item) {
+                let prefix = &path[..path.len() - node.key.len() + i];
+                if let (false, Some(value)) = (prefix.is_empty(), node.value.clone()) {
+                    base.push((prefix.to_vec(), value));
+                }
+            }
+        });
+        base
+    }
+
+    /// Returns the number of distinct nodes reachable from the root.
+    pub fn node_count(&self) -> usize {
+        let mut seen = HashSet::new();
+        self.root.children().map(|x| x.count_nodes(&mut seen)).sum()
+    }
+
+    /// Returns an estimate of the bytes the tree takes up, itself
+    /// included: every node with its reference counts, the spare capacity
+    /// of the keys and children vectors, and the node index.
+    ///
+    /// Hash maps are estimated from their capacity, so the figure is not
+    /// exact, but it grows and shrinks with the tree.
+    pub fn memory_bytes(&self) -> usize {
+        let node = |x: &Node<K, V>| {
+            x.key.capacity() * mem::size_of::<K>()
+                + x.children.nodes.capacity() * mem::size_of::<Arc<Node<K, V>>>()
+                + map_bytes(&x.children.positions)
+        };
+        let shared = mem::size_of::<Node<K, V>>() + 2 * mem::size_of::<usize>();
+        let nodes: usize = self.bfs().map(|x| shared + node(x)).sum();
+        let index: usize = self.nodeindex.values().map(map_bytes).sum();
+        mem::size_of::<Self>() + node(&self.root) + nodes + map_bytes(&self.nodeindex) + index
+    }
+
+    /// Returns the number of nodes without children. Together with
+    /// [`num_internal`](#method.num_internal) they make up
+    /// [`node_count`](#method.node_count); the root is counted by neither.
+    pub fn num_leaves(&self) -> usize {
+        self.bfs().filter(|x| x.is_leaf()).count()
+    }
+
+    /// Returns the number of nodes with at least one child, see
+    /// [`num_leaves`](#method.num_leaves).
+    pub fn num_internal(&self) -> usize {
+        self.bfs().filter(|x| !x.is_leaf()).count()
+    }
+
+    /// Returns the number of nodes on the longest root-to-leaf path, so a
+    /// tree holding only `[3]`, `[3, 137]` and `[3, 137, 2]` has height 3.
+    /// An empty tree has height 0.
+    pub fn height(&self) -> usize {
+        self.root.children().map(|x| x.height()).max().unwrap_or(0)
+    }
+
+    /// Returns how many times a key was appended, counting repeated keys
+    /// once per append.
+    pub fn total_inserts(&self) -> u64 {
+        self.count
+    }
+
+    /// Removes all keys, keeping the memory allocated for the node index.
+    pub fn clear(&mut self) {
+        self.root = Node::root();
+        self.nodeindex.clear();
+        self.count = 0;
+    }
+
+    /// Frees the memory the node index holds beyond what its entries
+    /// need, as after a bulk load followed by pruning or removals.
+    pub fn shrink_to_fit(&mut self) {
+        for nodes in self.nodeindex.values_mut() {
+            nodes.shrink_to_fit();
+        }
+        self.nodeindex.shrink_to_fit();
+    }
+
+    /// Makes room in the node index for at least `additional` more
+    /// distinct items, ahead of a bulk load.
+    pub fn reserve(&mut self, additional: usize) {
+        self.nodeindex.reserve(additional);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.root.children.is_empty() && !self.root.terminal
+    }
+
+    pub fn average_key_len(&self) -> f64 {
+        let c = self.root.count_key_len();
+        c.1 as f64/(c.0 - 1) as f64
+    }
+}
+
+impl<K: Eq + Hash + Clone, V: Weight> PrefixTree<K, V> {
+    fn insert<Q: AsRef<[K]>>(&mut self, key: Q, weight: V) {
+        let key = key.as_ref();
+        let key = match self.max_depth {
+            Some(max) if key.len() > max => &key[..max],
+            _ => key,
+        };
+        self.root.append(key, weight, &mut self.nodeindex);
+    }
+
+    /// Appends `key`, adding `weight` to the value of each node on its path.
+    pub fn append_weighted<Q: AsRef<[K]>>(&mut self, key: Q, weight: V) {
+        self.insert(key, weight);
+        self.count += 1;
+    }
+}
+
+impl<K: Eq + Hash + Clone, V: Count> PrefixTree<K, V> {
+    /// Appends `key`, adding one to the value of each node on its path.
+    pub fn append<Q: AsRef<[K]>>(&mut self, key: Q) {
+        self.append_weighted(key, V::one())
+    }
+
+    /// Appends every key of `transactions`, returning how many there were.
+    pub fn append_all<I, Q>(&mut self, transactions: I) -> usize
+        where I: IntoIterator<Item = Q>, Q: AsRef<[K]>
+    {
+        let mut n = 0;
+        for key in transactions {
+            self.append(key);
+            n += 1;
+        }
+        n
+    }
+}
+
+impl<K: Eq + Hash + Clone> PrefixTree<K, u32> {
+    /// Adds the keys of `other` to this tree, summing the counts of keys
+    /// present in both.
+    pub fn merge(&mut self, other: &PrefixTree<K, u32>) {
+        for (key, n) in other.own_counts() {
+            self.append_with_count(key, n);
+        }
+    }
+
+    /// Adds the keys of `other` to this tree, setting the count of keys
+    /// present in both to `combine` of their count here and in `other`.
+    /// Counts are those of [`entry`](#method.entry); keys only this tree
+    /// has are left as they are.
+    pub fn merge_with<F: Fn(u32, u32) -> u32>(&mut self, other: &PrefixTree<K, u32>, combine: F) {
+        for (key, n) in other.own_counts() {
+            self.update_with(key, |m| m.map_or(n, |m| combine(m, n)));
+        }
+    }
+
+    /// Returns every key appended a different number of times to this
+    /// tree than to `other`, with the count in this tree minus the count
+    /// in `other`. Keys of this tree come first, in the order of
+    /// [`iter`](#method.iter), followed by the keys only `other` has.
+    pub fn diff(&self, other: &PrefixTree<K, u32>) -> Vec<(Vec<K>, i64)> {
+        let mut theirs: HashMap<Vec<K>, u32> = other.own_counts().into_iter().collect();
+        let mut out = Vec::new();
+        for (key, n) in self.own_counts() {
+            let m = theirs.remove(&key).unwrap_or(0);
+            if n != m {
+                out.push((key, i64::from(n) - i64::from(m)));
+            }
+        }
+        for (key, m) in other.own_counts() {
+            if theirs.contains_key(&key) && m > 0 {
+                out.push((key, -i64::from(m)));
+            }
+        }
+        out
+    }
+
+    /// Returns every appended key with how often it was appended itself,
+    /// the counts of longer keys passing through it left out.
+    /// [`from_own_counts`](#method.from_own_counts) rebuilds the tree from
+    /// them.
+    fn own_counts(&self) -> Vec<(Vec<K>, u32)> {
+        let mut keys = Vec::new();
+        if self.root.terminal {
+            keys.push((Vec::new(), self.root.own_count()));
+        }
+        self.walk(|path, node| {
+            if node.terminal {
+                keys.push((path.to_vec(), node.own_count()));
+            }
+        });
+        keys
+    }
+
+    /// Builds a tree from keys and their own counts as
+    /// [`own_counts`](#method.own_counts) gives them. Unlike
+    /// [`append_with_count`](#method.append_with_count), keys with a count
+    /// of zero are kept, so the keys of the tree are restored exactly.
+    #[cfg(any(feature = "serde", not(feature = "no_std")))]
+    pub(crate) fn from_own_counts<I: IntoIterator<Item = (Vec<K>, u32)>>(keys: I) -> PrefixTree<K, u32> {
+        let mut tree = PrefixTree::new();
+        for (key, n) in keys {
+            tree.insert(key, n);
+            tree.count += u64::from(n);
+        }
+        tree
+    }
+
+    /// Appends `key` `n` times in one pass.
+    pub fn append_with_count<Q: AsRef<[K]>>(&mut self, key: Q, n: u32) {
+        if n == 0 {
+            return;
+        }
+        self.insert(key, n);
+        self.count += u64::from(n);
+    }
+
+    /// Builds a tree from distinct keys and their counts as
+    /// [`get_count`](#method.get_count) and
+    /// [`to_hashmap`](#method.to_hashmap) give them, so that
+    /// `from_pairs(t.to_hashmap())` equals `t`.
+    ///
+    /// Those counts include the appends of longer keys, so keys are
+    /// appended longest first, each as often as its count exceeds what the
+    /// longer keys already add to it. A key whose count they cover entirely
+    /// is only kept as a prefix of them.
+    pub fn from_pairs<I: IntoIterator<Item = (Vec<K>, u32)>>(pairs: I) -> PrefixTree<K, u32> {
+        let mut pairs: Vec<_> = pairs.into_iter().collect();
+        pairs.sort_by_key(|(key, _)| cmp::Reverse(key.len()));
+        let mut tree = PrefixTree::new();
+        for (key, n) in pairs {
+            let below = tree.root.find_prefix(&key, &mut Vec::new()).and_then(|x| x.value).unwrap_or(0);
+            tree.append_with_count(key, n.saturating_sub(below));
+        }
+        tree
+    }
+
+    /// Returns the keys starting with `prefix` as a tree of their own, keyed
+    /// by what follows `prefix` and with their counts copied, or `None` if
+    /// no key starts with `prefix`. `prefix` itself, if it was appended,
+    /// becomes the empty key.
+    pub fn subtree<Q: AsRef<[K]>>(&self, prefix: Q) -> Option<PrefixTree<K, u32>> {
+        let prefix = prefix.as_ref();
+        let mut path = Vec::new();
+        let node = self.root.find_prefix(prefix, &mut path)?;
+        let count = node.value?;
+        // The prefix may end in the middle of the node's key.
+        let offset = prefix.len() - path.len();
+        let root = if offset < node.key.len() {
+            let mut child = node.deep_clone();
+            child.key.drain(..offset);
+            let mut root = Node::root();
+            root.value = Some(count);
+            root.children.push(Arc::new(child));
+            root
+        } else {
+            let mut root = node.deep_clone();
+            root.key.clear();
+            root
+        };
+        let mut tree = PrefixTree { root, count: u64::from(count), ..PrefixTree::new() };
+        tree.reindex();
+        Some(tree)
+    }
+
+    /// Returns the sum of the counts [`get_count`](#method.get_count) gives
+    /// for the appended keys starting with `prefix`, `prefix` included, or
+    /// `None` if no key starts with it.
+    ///
+    /// Those counts already include the appends of longer keys, so unlike
+    /// the count stored at the prefix this weighs every append by how many
+    /// of the keys below `prefix` it passes through.
+    pub fn subtree_sum<Q: AsRef<[K]>>(&self, prefix: Q) -> Option<u32> {
+        let node = self.root.find_prefix(prefix.as_ref(), &mut Vec::new())?;
+        let mut sum = None;
+        let mut stack = vec![node];
+        while let Some(node) = stack.pop() {
+            if node.terminal {
+                sum = Some(sum.unwrap_or(0u32).saturating_add(node.value.unwrap_or(0)));
+            }
+            stack.extend(node.children());
+        }
+        sum
+    }
+
+    /// Returns the `k` appended keys with the highest counts, as
+    /// [`get_count`](#method.get_count) gives them, highest first and equal
+    /// counts in ascending order of keys. Fewer keys are returned if the
+    /// tree holds fewer.
+    ///
+    /// Only the best `k` keys found so far are kept while walking the tree.
+    pub fn top_k(&self, k: usize) -> Vec<(Vec<K>, u32)> where K: Ord {
+        let mut heap: BinaryHeap<Reverse<(u32, Reverse<Vec<K>>)>> = BinaryHeap::with_capacity(k + 1);
+        let mut offer = |key: &[K], count: u32| {
+            if heap.len() == k {
+                let better = match heap.peek() {
+                    Some(&Reverse((min, Reverse(ref worst)))) => count > min || count == min && key < &worst[..],
+                    None => false,
+                };
+                if !better {
+                    return;
+                }
+            }
+            heap.push(Reverse((count, Reverse(key.to_vec()))));
+            if heap.len() > k {
+                heap.pop();
+            }
+        };
+        if self.root.terminal {
+            offer(&[], self.root.value.unwrap_or(0));
+        }
+        self.walk(|path, node| {
+            if node.terminal {
+                offer(path, node.value.unwrap_or(0));
+            }
+        });
+        heap.into_sorted_vec().into_iter().map(|Reverse((count, Reverse(key)))| (key, count)).collect()
+    }
+
+    /// Returns the entry of `key` for reading or changing how often it
+    /// was appended, see [`Entry`](enum.Entry.html).
+    pub fn entry<Q: AsRef<[K]>>(&mut self, key: Q) -> Entry<'_, K> {
+        let mut key = key.as_ref().to_vec();
+        if let Some(max) = self.max_depth {
+            key.truncate(max);
+        }
+        Entry::new(self, key)
+    }
+
+    /// Returns the count of `key` for changing in place, or `None` unless
+    /// `key` was appended (see [`contains`](#method.contains)).
+    ///
+    /// This edits how often `key` was appended itself, as
+    /// [`entry`](#method.entry) does, not the value
+    /// [`get_count`](#method.get_count) returns: that one also counts the
+    /// appends of longer keys passing through `key`, and changes by as much
+    /// as the own count does. The counts along the key's path are updated
+    /// when the returned [`CountMut`](struct.CountMut.html) is dropped; a
+    /// count of zero removes the key.
+    ///
+    /// ```
+    /// let mut t = prefix_tree::Tree::new();
+    /// t.append([3, 137]);
+    /// t.append([3, 137, 2]);
+    /// assert_eq!(t.get_count([3, 137]), Some(2));
+    ///
+    /// let mut count = t.get_mut([3, 137]).unwrap();
+    /// assert_eq!(*count, 1);
+    /// *count += 5;
+    /// drop(count);
+    /// assert_eq!(t.get_count([3, 137]), Some(7));
+    /// ```
+    pub fn get_mut<Q: AsRef<[K]>>(&mut self, key: Q) -> Option<CountMut<'_, K>> {
+        match self.entry(key) {
+            Entry::Occupied(e) => Some(CountMut::new(e)),
+            Entry::Vacant(_) => None,
+        }
+    }
+
+    /// Sets the count of `key` to what `f` returns for its current count,
+    /// `None` if it was never appended. Counts are those of
+    /// [`entry`](#method.entry): `append` is `update_with(key, |n|
+    /// n.unwrap_or(0) + 1)`, and a new count of zero removes the key.
+    pub fn update_with<Q: AsRef<[K]>, F: FnOnce(Option<u32>) -> u32>(&mut self, key: Q, f: F) {
+        match self.entry(key) {
+            Entry::Occupied(mut e) => {
+                let count = f(Some(e.get()));
+                e.insert(count);
+            },
+            Entry::Vacant(e) => {
+                e.insert(f(None));
+            },
+        }
+    }
+
+    /// Removes `key`, returning how often it had been appended.
+    ///
+    /// Longer keys passing through `key` are kept; only the appends of
+    /// `key` itself are taken off the counts along its path, and nodes
+    /// which are no longer used are pruned.
+    pub fn remove<Q: AsRef<[K]>>(&mut self, key: Q) -> Option<u32> {
+        let key = key.as_ref();
+        let weight = self.find(key).filter(|n| n.terminal)?.own_count();
+        self.root.remove(key, weight, &mut self.nodeindex);
+        self.count = self.count.saturating_sub(u64::from(weight));
+        Some(weight)
+    }
+
+    /// Removes every key starting with `prefix`, `prefix` included,
+    /// returning how many distinct keys were removed. The prefix may end in
+    /// the middle of a node's key, which drops that node and everything
+    /// below it.
+    pub fn remove_prefix<Q: AsRef<[K]>>(&mut self, prefix: Q) -> usize {
+        let prefix = prefix.as_ref();
+        if prefix.is_empty() {
+            let removed = self.distinct_keys();
+            self.clear();
+            return removed;
+        }
+        let (keys, appends) = self.root.remove_prefix(prefix, &mut self.nodeindex);
+        self.count = self.count.saturating_sub(u64::from(appends));
+        keys
+    }
+
+    /// Takes one off the count of every node on the path of `key`, the
+    /// inverse of [`append`](#method.append), returning whether one of
+    /// `key`'s own appends was taken off.
+    ///
+    /// Unlike [`remove`](#method.remove) nodes are kept even when their
+    /// count drops to zero, so the structure is ready for later appends.
+    /// A key whose own appends are all taken off is left unchanged and
+    /// false is returned, so its prefix count never drops below that of
+    /// the longer keys passing through it.
+    pub fn decrement<Q: AsRef<[K]>>(&mut self, key: Q) -> bool {
+        let key = key.as_ref();
+        match self.find(key) {
+            Some(node) if node.terminal && node.own_count() > 0 => {},
+            _ => return false,
+        }
+        self.root.decrement(key, &mut self.nodeindex);
+        self.count = self.count.saturating_sub(1);
+        true
+    }
+
+    /// Keeps only the keys for which `f`, given the key and its count,
+    /// returns true, removing the others as [`remove`](#method.remove)
+    /// does. Longer keys passing through a removed key are kept.
+    pub fn retain<F: FnMut(&[K], u32) -> bool>(&mut self, mut f: F) {
+        let removed: Vec<_> = self.iter()
+            .filter(|&(ref key, count)| !f(key, count))
+            .map(|(key, _)| key)
+            .collect();
+        for key in removed {
+            self.remove(key);
+        }
+    }
+
+    /// Multiplies every count by `factor`, which must lie between 0 and 1,
+    /// so that keys appended afterwards weigh more than the ones before.
+    ///
+    /// Counts are rounded down, each on its own, so a node never ends up
+    /// counting more than its parent. [`total_inserts`](#method.total_inserts)
+    /// decays the same way. Nodes decaying to zero are kept; follow with
+    /// [`prune_below(1)`](#method.prune_below) to drop them.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `factor` is not between 0 and 1, or is NaN.
+    pub fn decay(&mut self, factor: f64) {
+        assert!((0.0..=1.0).contains(&factor), "decay factor {} is not between 0 and 1", factor);
+        self.root.decay(factor, &mut self.nodeindex);
+        self.count = (self.count as f64 * factor) as u64;
+    }
+
+    /// Multiplies every count by `factor`, as if every key had been
+    /// appended `factor` times as often. Counts saturate at `u32::MAX`;
+    /// [`total_inserts`](#method.total_inserts) is scaled without them.
+    pub fn scale_counts(&mut self, factor: u32) {
+        self.root.scale(factor, &mut self.nodeindex);
+        self.count = self.count.saturating_mul(u64::from(factor));
+    }
+
+    /// Drops every node counting less than `min_count` along with the
+    /// nodes below it, which count no more than it does.
+    ///
+    /// The appends of the keys dropped are taken off the counts of the
+    /// nodes above them and off [`total_inserts`](#method.total_inserts),
+    /// so the nodes kept count only the keys still stored. Counts are
+    /// compared before any are lowered, so a node may end up below
+    /// `min_count`. Nodes left holding no key are dropped.
+    pub fn prune_below(&mut self, min_count: u32) {
+        let pruned = self.root.prune_below(min_count, &mut self.nodeindex);
+        self.count = self.count.saturating_sub(pruned);
+    }
+}
+
+impl<K: Clone, V: Clone> IntoIterator for PrefixTree<K, V> {
+    type Item = (Vec<K>, V);
+    type IntoIter = IntoIter<K, V>;
+
+    fn into_iter(self) -> IntoIter<K, V> {
+        // Dropping the index first leaves the nodes reachable from the
+        // root only, so they can be taken apart rather than copied.
+        let PrefixTree { root, nodeindex, .. } = self;
+        drop(nodeindex);
+        IntoIter {
+            stack: vec![(Arc::new(root), 0)],
+            path: Vec::new(),
+        }
+    }
+}
+
+impl<'a, K: Clone, V: Clone> IntoIterator for &'a PrefixTree<K, V> {
+    type Item = (Vec<K>, V);
+    type IntoIter = Iter<'a, K, V>;
+
+    fn into_iter(self) -> Iter<'a, K, V> {
+        Iter {
+            stack: vec![(&self.root, 0, 0)],
+            path: Vec::new(),
+            max_depth: usize::MAX,
+        }
+    }
+}
+
+impl<K: Eq + Hash + Clone, V: Count, Q: AsRef<[K]>> FromIterator<Q> for PrefixTree<K, V> {
+    fn from_iter<I: IntoIterator<Item = Q>>(transactions: I) -> PrefixTree<K, V> {
+        let mut tree = PrefixTree::new();
+        tree.append_all(transactions);
+        tree
+    }
+}
+
+impl<K: Eq + Hash + Clone, V: Count, Q: AsRef<[K]>> Extend<Q> for PrefixTree<K, V> {
+    fn extend<I: IntoIterator<Item = Q>>(&mut self, transactions: I) {
+        self.append_all(transactions);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{common_prefix_len, node_addr, Branches, Node, PrefixTree, Tree};
+    use prelude::*;
+    use std::thread;
+
+    #[test]
+    fn test_common_prefix_empty() {
+        assert!(Node::new(vec![3u32, 137u32, 2u32], ()).common_prefix([]) == 0);
+        assert_eq!(common_prefix_len(&[3u32, 137u32, 2u32], &[]), 0);
+        assert_eq!(common_prefix_len::<u32>(&[], &[]), 0);
+    }
+
+    #[test]
+    fn test_common_prefix_short() {
+        assert!(Node::new(vec![3u32, 137u32, 2u32], ()).common_prefix(vec![3u32, 137u32, 8u32, 2u32]) == 2);
+        assert_eq!(common_prefix_len(&[3u32, 137u32, 2u32], &[3u32, 137u32, 8u32, 2u32]), 2);
+        assert_eq!(common_prefix_len(&[3u32, 137u32], &[3u32, 137u32, 2u32]), 2);
+    }
+
+    #[test]
+    fn test_find_empty() {
+        let t = Tree::new();
+        assert!(t.find([]).is_none());
+        assert!(t.find(vec![3u32, 137u32, 2u32]).is_none());
+    }
+
+    fn sample_tree() -> Tree {
+        let mut t = Tree::new();
+        t.append(vec![3u32, 137u32]);
+        t.append(vec![3u32, 137u32, 137u32]);
+        t.append(vec![1u32, 2u32, 9u32]);
+        t
+    }
+
+    #[test]
+    fn test_sample_tree_nodeindex() {
+        let t = &sample_tree();
+        let ni = &t.nodeindex;
+        println!("node index: {:?}", ni);
+        assert!(ni.len() == 5);
+        let nodes_3 = ni.get(&3).unwrap();
+        assert!(nodes_3.len() == 1);
+        let n1 = t.find(vec![3u32, 137u32]).unwrap();
+        println!("n1: {:?}", n1);
+        assert!(nodes_3.contains_key(&node_addr(n1)));
+        let chain: Vec<_> = t.node_chain(&3).map(node_addr).collect();
+        assert_eq!(chain, vec![node_addr(n1)]);
+        assert_index_live(t);
+    }
+
+    /// Asserts that the node index holds exactly the nodes reachable from
+    /// the root, each under every item of its key.
+    fn assert_index_live(t: &Tree) {
+        let mut expected: HashMap<u32, HashSet<usize>> = HashMap::new();
+        t.walk(|_, node| {
+            for k in &node.key {
+                expected.entry(*k).or_default().insert(node_addr(node));
+            }
+        });
+        let actual: HashMap<u32, HashSet<usize>> = t.nodeindex.iter()
+            .map(|(k, nodes)| (*k, nodes.keys().cloned().collect()))
+            .collect();
+        assert_eq!(actual, expected);
+        for nodes in t.nodeindex.values() {
+            for (addr, node) in nodes {
+                assert_eq!(*addr, node_addr(node));
+            }
+        }
+    }
+
+    #[test]
+    fn test_nodeindex_live() {
+        let mut t = Tree::new();
+        assert_index_live(&t);
+        t.append(vec![3u32, 137u32, 2u32]);
+        t.append(vec![3u32, 137u32, 99u32, 22u32]);
+        t.append(vec![3u32, 137u32]);
+        t.append(vec![3u32]);
+        assert_index_live(&t);
+        assert_eq!(t.nodeindex[&3].len(), 1);
+        assert_eq!(t.nodeindex[&137].len(), 1);
+        assert_index_live(&sample_apriori_tree());
+        assert_eq!(sample_apriori_tree().nodeindex[&8].len(), 3);
+        t.decrement(vec![3u32, 137u32, 2u32]);
+        assert_index_live(&t);
+        t.remove(vec![3u32, 137u32, 99u32, 22u32]);
+        assert_index_live(&t);
+        assert!(!t.nodeindex.contains_key(&99));
+        assert_index_live(&t.clone());
+    }
+
+    #[test]
+    fn test_validate() {
+        assert_eq!(Tree::new().validate(), Ok(()));
+        assert_eq!(sample_tree().validate(), Ok(()));
+        assert_eq!(sample_apriori_tree().validate(), Ok(()));
+
+        let mut t = sample_tree();
+        let mut branches = Branches::new();
+        branches.push(Arc::new(Node::new(vec![1, 2], 1)));
+        branches.push(Arc::new(Node::new(vec![1, 3], 1)));
+        t.root.children = branches;
+        assert!(t.validate().unwrap_err().contains("share the prefix"));
+
+        t.root = Node::root();
+        t.root.children.push(Arc::new(Node::new(vec![1, 2], 1)));
+        assert!(t.validate().unwrap_err().contains("missing from the index"));
+        t.reindex();
+        assert_eq!(t.validate(), Ok(()));
+        t.nodeindex.entry(7).or_default().insert(0, Arc::new(Node::new(vec![7], 1)));
+        assert!(t.validate().unwrap_err().contains("stale"));
+
+        let mut node = Node::new(vec![1], 1);
+        node.terminal = false;
+        node.children.push(Arc::new(Node::new(vec![2], 2)));
+        t.root = Node::root();
+        t.root.children.push(Arc::new(node));
+        t.reindex();
+        assert!(t.validate().unwrap_err().contains("counts more than its parent"));
+
+        t.root = Node::root();
+        let mut node = Node::new(vec![1], 1);
+        node.value = None;
+        t.root.children.push(Arc::new(node));
+        t.reindex();
+        assert!(t.validate().unwrap_err().contains("has no value"));
+    }
+
+    #[test]
+    fn test_node_chain() {
+        let t = sample_apriori_tree();
+        for item in 0..10 {
+            let mut expected = HashSet::new();
+            t.walk(|_, node| {
+                if node.key.contains(&item) {
+                    expected.insert(node_addr(node));
+                }
+            });
+            let chain: HashSet<_> = t.node_chain(&item).map(node_addr).collect();
+            assert_eq!(chain, expected);
+        }
+        assert_eq!(t.node_chain(&8).count(), 3);
+        assert_eq!(t.node_chain(&8).map(|n| n.value.unwrap()).sum::<u32>(), 8);
+        assert_eq!(t.node_chain(&999).count(), 0);
+    }
+
+    #[test]
+    fn test_full_key() {
+        let t = sample_tree();
+        let grandchild = t.find([3, 137, 137]).unwrap();
+        assert_eq!(t.full_key(grandchild), Some(vec![3, 137, 137]));
+        let mut keys: Vec<Vec<u32>> = t.node_chain(&2).filter_map(|x| t.full_key(x)).collect();
+        keys.sort();
+        assert_eq!(keys, vec![vec![1, 2, 9]]);
+        assert_eq!(t.full_key(&Node::new(vec![3], 1)), None);
+        // copies are separate nodes
+        assert_eq!(t.clone().full_key(grandchild), None);
+    }
+
+    #[test]
+    fn test_find_ancestors() {
+        let t = sample_apriori_tree();
+        let node = t.find([8, 6, 2, 5, 4, 1]).unwrap();
+        let keys: Vec<&[u32]> = t.find_ancestors(node).unwrap().iter().map(|x| &x.key[..]).collect();
+        assert_eq!(keys, vec![&[8][..], &[6], &[2]]);
+        assert_eq!(t.find_parent(node).unwrap().key, vec![2]);
+        assert_eq!(t.find_parent(t.find([2, 8, 4, 0, 7]).unwrap()), None);
+        assert_eq!(t.find_ancestors(t.find([2, 8, 4, 0, 7]).unwrap()), Some(vec![]));
+        assert_eq!(t.find_ancestors(&Node::new(vec![8], 1)), None);
+    }
+
+    #[test]
+    fn test_path_to_root() {
+        let t = sample_tree();
+        assert_eq!(t.path_to_root([3, 137, 137]), Some(vec![vec![137], vec![3, 137]]));
+        assert_eq!(t.path_to_root([1, 2, 9]), Some(vec![vec![1, 2, 9]]));
+        assert_eq!(t.path_to_root([3]), None);
+        assert_eq!(t.path_to_root([3, 137, 2]), None);
+        assert_eq!(t.path_to_root([]), None);
+
+        let t = sample_apriori_tree();
+        assert_eq!(t.path_to_root([8, 6, 2, 5, 4, 1]).unwrap(),
+                   vec![vec![5, 4, 1], vec![2], vec![6], vec![8]]);
+        let mut t = t;
+        t.append(vec![]);
+        assert_eq!(t.path_to_root([]), Some(vec![]));
+    }
+
+    #[test]
+    fn test_drop_frees_nodes() {
+        let t = sample_apriori_tree();
+        let weak: Vec<_> = t.nodeindex.values()
+            .flat_map(|x| x.values())
+            .map(Arc::downgrade)
+            .collect();
+        assert!(!weak.is_empty());
+        drop(t);
+        assert!(weak.iter().all(|x| x.upgrade().is_none()));
+    }
+
+    #[test]
+    fn test_conditional_pattern_base() {
+        let t = sample_apriori_tree();
+        assert_eq!(t.conditional_pattern_base(&5), vec![
+            (vec![8], 2),
+            (vec![8, 6, 2], 1),
+            (vec![8, 6], 1),
+        ]);
+        assert_eq!(t.conditional_pattern_base(&8), vec![(vec![6], 1), (vec![2], 1)]);
+        assert_eq!(t.conditional_pattern_base(&7), vec![
+            (vec![6, 2, 4], 1),
+            (vec![2, 8, 4, 0], 1),
+            (vec![1], 1),
+        ]);
+        assert!(t.conditional_pattern_base(&999).is_empty());
+    }
+
+    #[test]
+    fn test_node_count() {
+        assert_eq!(Tree::new().node_count(), 0);
+        assert!(Tree::new().is_empty());
+        assert_eq!(sample_tree().node_count(), 3);
+        assert!(!sample_tree().is_empty());
+        assert_eq!(sample_apriori_tree().node_count(), 15);
+    }
+
+    #[test]
+    fn test_height() {
+        assert_eq!(Tree::new().height(), 0);
+        assert_eq!(sample_tree().height(), 2);
+        assert_eq!(sample_apriori_tree().height(), 4);
+    }
+
+    #[test]
+    fn test_total_inserts() {
+        assert_eq!(Tree::new().total_inserts(), 0);
+        assert_eq!(sample_tree().total_inserts(), 3);
+        assert_eq!(sample_apriori_tree().total_inserts(), 10);
+    }
+
+    #[test]
+    fn test_sample_tree_average_key_len() {
+        assert!(sample_tree().average_key_len() == 2.0);
+    }
+
+    #[test]
+    fn test_find_simple() {
+        assert!(sample_tree().find(vec![3u32, 137u32]).unwrap().value == Some(2));
+    }
+
+    #[test]
+    fn test_find_child() {
+        assert!(sample_tree().find(vec![3u32, 137u32, 137u32]).unwrap().value == Some(1));
+    }
+
+    #[test]
+    fn test_find_sibling() {
+        assert!(sample_tree().find(vec![1u32, 2u32, 9u32]).unwrap().value == Some(1));
+    }
+
+    #[test]
+    fn test_find_missing() {
+        assert!(sample_tree().find(vec![999u32]).is_none());
+    }
+
+    #[test]
+    fn test_find_shorter() {
+        assert!(sample_tree().find(vec![3u32]).is_none());
+    }
+
+     #[test]
+    fn test_find_longer() {
+        assert!(sample_tree().find(vec![3u32, 137u32, 137u32, 137u32]).is_none());
+    }
+
+    #[test]
+    fn test_find_many_siblings() {
+        let mut t = Tree::new();
+        for i in 0..1000u32 {
+            t.append([i]);
+            t.append([i, i + 1]);
+        }
+        assert_eq!(t.get_count([999]), Some(2));
+        assert_eq!(t.get_count([999, 1000]), Some(1));
+        assert!(t.find([999, 999]).is_none());
+        assert!(t.find([1000]).is_none());
+    }
+
+    #[test]
+    fn test_find_wide_fanout() {
+        let mut t = Tree::new();
+        for i in 0..100_000u32 {
+            t.append([i, i]);
+        }
+        for i in 0..100_000u32 {
+            assert_eq!(t.get_count([i, i]), Some(1));
+        }
+        assert_eq!(t.node_count(), 100_000);
+        assert!(t.find([100_000]).is_none());
+        assert_eq!(t.validate(), Ok(()));
+    }
+
+    #[test]
+    fn test_drop_deep_tree() {
+        // appending every prefix of a long key builds the same chain, but
+        // takes quadratic time
+        let mut chain = Node::new(vec![0u32], 1u32);
+        for item in 1..200_000 {
+            let mut parent = Node::new(vec![item], 1);
+            parent.children.push(Arc::new(chain));
+            chain = parent;
+        }
+        let mut t = Tree::new();
+        t.root.children.push(Arc::new(chain));
+        t.reindex();
+        t.count = 1;
+        assert_eq!(t.bfs().count(), 200_000);
+        drop(t);
+    }
+
+    #[test]
+    fn test_append_missing_value() {
+        let mut t = Tree::new();
+        let mut node = Node::new(vec![1, 2], 1);
+        node.value = None;
+        t.root.children.push(Arc::new(node));
+        t.reindex();
+        t.append(vec![1, 2]);
+        t.append(vec![1, 3]);
+        assert_eq!(t.get_count([1, 2]), Some(1));
+        assert_eq!(t.find([1]).unwrap().value, Some(2));
+
+        t.root.value = None;
+        t.append(vec![]);
+        assert_eq!(t.get_count([]), Some(1));
+    }
+
+    #[test]
+    fn test_empty_key() {
+        let mut t = sample_tree();
+        t.append(Vec::<u32>::new());
+        assert!(t.contains([]));
+        assert_eq!(t.get_count([]), Some(4));
+        assert_eq!(t.distinct_keys(), 4);
+        assert_eq!(t.iter().next(), Some((vec![], 4)));
+        assert_eq!(t.longest_prefix([7]), Some((vec![], 4)));
+        assert_eq!(t.validate(), Ok(()));
+        assert_eq!(t.remove(Vec::<u32>::new()), Some(1));
+        assert!(!t.contains([]));
+        assert_eq!(t, sample_tree());
+    }
+
+    #[test]
+    fn test_contains() {
+        let t = sample_tree();
+        assert!(t.contains(vec![3u32, 137u32]));
+        assert!(t.contains(vec![3u32, 137u32, 137u32]));
+        assert!(t.contains(vec![1u32, 2u32, 9u32]));
+        assert!(!t.contains(vec![3u32]));
+        assert!(!t.contains(vec![3u32, 137u32, 137u32, 137u32]));
+        assert!(!t.contains(vec![999u32]));
+        assert!(!Tree::new().contains(vec![3u32]));
+    }
+
+    #[test]
+    fn test_get_count() {
+        let t = sample_tree();
+        assert_eq!(t.get_count(vec![3u32, 137u32]), Some(2));
+        assert_eq!(t.get_count(vec![3u32, 137u32, 137u32]), Some(1));
+        assert_eq!(t.get_count(vec![3u32]), None);
+        assert_eq!(t.get_count(vec![999u32]), None);
+    }
+
+    #[test]
+    fn test_insert_empty() {
+        let mut t = Tree::new();
+        t.append(vec![999u32]);
+        let top = children(&t.root);
+        assert_eq!(top.len(), 1);
+        assert!(top[0].key == vec![999u32]);
+        assert!(top[0].value == Some(1));
+        assert!(top[0].children.is_empty());
+    }
+
+    #[test]
+    fn test_insert_append() {
+        let mut t = Tree::new();
+        t.append(vec![3u32]);
+        t.append(vec![3u32, 137u32]);
+        t.append(vec![3u32, 137u32, 2u32]);
+        assert_eq!(t.height(), 3);
+        let top = children(&t.root);
+        assert_eq!(top.len(), 1);
+        let foo = top[0];
+        assert!(foo.key == vec![3u32]);
+        assert!(foo.value == Some(3));
+        let below_foo = children(foo);
+        assert_eq!(below_foo.len(), 1);
+        let bar = below_foo[0];
+        assert!(bar.key == vec![137u32]);
+        assert!(bar.value == Some(2));
+        let below_bar = children(bar);
+        assert_eq!(below_bar.len(), 1);
+        let baz = below_bar[0];
+        assert!(baz.key == vec![2u32]);
+        assert!(baz.value == Some(1));
+        assert!(baz.children.is_empty());
+    }
+
+    #[test]
+    fn test_insert_sibling() {
+        let mut t = Tree::new();
+        t.append(vec![987u32]);
+        t.append(vec![654u32]);
+        t.append(vec![321u32]);
+        let top = children(&t.root);
+        assert_eq!(top.len(), 3);
+        for (node, key) in top.iter().zip(&[987u32, 654u32, 321u32]) {
+            assert!(node.key == vec![*key]);
+            assert!(node.value == Some(1));
+            assert!(node.children.is_empty());
+        }
+    }
+
+    #[test]
+    fn test_insert_split() {
+        let mut t = Tree::new();
+        t.append(vec![3u32, 137u32, 2u32]);
+        println!("test_insert_split/pre: {:?}", t);
+        t.append(vec![3u32, 137u32, 99u32, 22u32]);
+        println!("test_insert_split/post: {:?}", t);
+        let top = children(&t.root);
+        assert_eq!(top.len(), 1);
+        let root = top[0];
+        assert!(root.key == vec![3u32, 137u32]);
+        assert!(root.value == Some(2));
+        let below = children(root);
+        assert_eq!(below.len(), 2);
+        let (foo, bar) = (below[0], below[1]);
+        assert!(foo.key == vec![2u32]);
+        assert!(foo.value == Some(1));
+        assert!(foo.children.is_empty());
+        assert!(bar.key == vec![99u32, 22u32]);
+        assert!(bar.value == Some(1));
+        assert!(bar.children.is_empty());
+        assert!(!root.is_terminal());
+        assert!(foo.is_terminal() && bar.is_terminal());
+        assert!(!root.is_leaf() && foo.is_leaf() && bar.is_leaf());
+        assert_eq!(root.child_count(), 2);
+        assert_eq!(foo.child_count(), 0);
+        assert_eq!(t.root.child_count(), 1);
+        assert!(!t.contains(vec![3u32, 137u32]));
+        assert_eq!(t.get_count(vec![3u32, 137u32]), None);
+        assert_eq!(t.distinct_keys(), 2);
+        assert!(t.iter().all(|(key, _)| key != vec![3u32, 137u32]));
+        t.append(vec![3u32, 137u32]);
+        assert_eq!(t.distinct_keys(), 3);
+        assert_eq!(t.get_count(vec![3u32, 137u32]), Some(3));
+
+        let mut expected = Tree::new();
+        expected.append(vec![3u32, 137u32]);
+        expected.append(vec![3u32, 137u32, 99u32, 22u32]);
+        expected.append(vec![3u32, 137u32, 2u32]);
+        assert_eq!(t, expected);
+    }
+
+    #[test]
+    fn test_insert_prefix_of_node() {
+        let mut t = Tree::new();
+        t.append(vec![3u32, 137u32, 2u32]);
+        t.append(vec![3u32, 137u32]);
+        let root = children(&t.root)[0];
+        assert!(root.key == vec![3u32, 137u32]);
+        assert!(root.value == Some(2));
+        assert!(root.is_terminal());
+        let foo = children(root)[0];
+        assert!(foo.key == vec![2u32]);
+        assert!(foo.value == Some(1));
+        assert!(foo.is_terminal());
+        assert!(t.contains(vec![3u32, 137u32]));
+        assert!(t.contains(vec![3u32, 137u32, 2u32]));
+        assert_eq!(t.distinct_keys(), 2);
+    }
+
+    #[test]
+    fn test_get_mut() {
+        let mut t = sample_tree();
+        let total = t.total_inserts();
+        *t.get_mut([3, 137]).unwrap() += 5;
+        assert_eq!(t.get_count([3, 137]), Some(7));
+        assert_eq!(t.get_count([3, 137, 137]), Some(1));
+        assert!(t.node_chain(&137).any(|x| x.value == Some(7)));
+        assert_index_live(&t);
+
+        *t.get_mut([1, 2, 9]).unwrap() = 4;
+        assert_eq!(t.get_count([1, 2, 9]), Some(4));
+        assert!(t.get_mut([3]).is_none());
+        assert!(t.get_mut([3, 2]).is_none());
+        assert!(Tree::new().get_mut([3]).is_none());
+        assert_eq!(t.total_inserts(), total + 8);
+        assert_eq!(t.validate(), Ok(()));
+        assert_index_live(&t);
+    }
+
+    #[test]
+    fn test_get_mut_keeps_counts() {
+        let mut t = Tree::new();
+        t.append(vec![1]);
+        t.append(vec![1, 2]);
+        *t.get_mut([1, 2]).unwrap() = 100;
+        assert_eq!(t.get_count([1]), Some(101));
+        assert_eq!(t.validate(), Ok(()));
+        assert_eq!(t.remove([1, 2]), Some(100));
+        assert_eq!(t.total_inserts(), 1);
+
+        *t.get_mut([1]).unwrap() = 100;
+        t.prune_below(1000);
+        assert!(t.is_empty());
+        assert_eq!(t.total_inserts(), 0);
+
+        t.append(vec![1]);
+        *t.get_mut([1]).unwrap() -= 1;
+        assert!(!t.contains([1]));
+        assert_eq!(t.validate(), Ok(()));
+    }
+
+    #[test]
+    fn test_longest_prefix() {
+        let t = sample_tree();
+        assert_eq!(t.longest_prefix(vec![3u32, 137u32, 9u32, 9u32]), Some((vec![3u32, 137u32], 2)));
+        assert_eq!(t.longest_prefix(vec![3u32, 137u32, 137u32]), Some((vec![3u32, 137u32, 137u32], 1)));
+        assert_eq!(t.longest_prefix(vec![1u32, 2u32, 9u32, 4u32]), Some((vec![1u32, 2u32, 9u32], 1)));
+        assert_eq!(t.longest_prefix(vec![1u32, 2u32]), None);
+        assert_eq!(t.longest_prefix(vec![3u32]), None);
+        assert_eq!(t.longest_prefix(vec![999u32]), None);
+        assert_eq!(t.longest_prefix(Vec::new()), None);
+        assert_eq!(Tree::new().longest_prefix(vec![3u32]), None);
+    }
+
+    #[test]
+    fn test_with_prefix() {
+        let t = sample_tree();
+        assert_eq!(t.with_prefix(vec![3u32]), vec![vec![3u32, 137u32], vec![3u32, 137u32, 137u32]]);
+        assert_eq!(t.with_prefix(vec![3u32, 137u32]), vec![vec![3u32, 137u32], vec![3u32, 137u32, 137u32]]);
+        assert_eq!(t.with_prefix(vec![3u32, 137u32, 137u32]), vec![vec![3u32, 137u32, 137u32]]);
+        assert_eq!(t.with_prefix(vec![1u32, 2u32]), vec![vec![1u32, 2u32, 9u32]]);
+        assert!(t.with_prefix(vec![3u32, 2u32]).is_empty());
+        assert!(t.with_prefix(vec![999u32]).is_empty());
+        assert_eq!(t.with_prefix(Vec::new()).len(), 3);
+        assert_eq!(sample_apriori_tree().with_prefix(Vec::new()).len(), 10);
+        assert_eq!(sample_apriori_tree().with_prefix(vec![8u32, 6u32]).len(), 3);
+    }
+
+    #[test]
+    fn test_subtree() {
+        let t = sample_tree();
+        let sub = t.subtree([3, 137]).unwrap();
+        assert_eq!(sub.iter().collect::<Vec<_>>(), vec![(vec![], 2), (vec![137], 1)]);
+        assert_eq!(sub.total_inserts(), 2);
+        assert_eq!(sub.validate(), Ok(()));
+        assert_eq!(sub, vec![vec![], vec![137]].into_iter().collect());
+
+        let sub = t.subtree([3]).unwrap();
+        assert_eq!(sub.iter().collect::<Vec<_>>(), vec![(vec![137], 2), (vec![137, 137], 1)]);
+        assert!(!sub.contains([]));
+        assert_eq!(sub.validate(), Ok(()));
+
+        assert_eq!(t.subtree([]).unwrap(), t);
+        assert!(t.subtree([3, 2]).is_none());
+        assert!(t.subtree([999]).is_none());
+        assert!(Tree::new().subtree([]).is_none());
+
+        let apriori = sample_apriori_tree();
+        let sub = apriori.subtree([8, 6]).unwrap();
+        assert_eq!(sub.find([2]).unwrap().value, Some(2));
+        assert_eq!(sub.distinct_keys(), 3);
+        assert_eq!(sub.validate(), Ok(()));
+    }
+
+    #[test]
+    fn test_map_values() {
+        let t = sample_apriori_tree();
+        let doubled = t.map_values(|c| c * 2);
+        assert_eq!(doubled.get_count([8, 5, 1, 3]), Some(2));
+        assert_eq!(doubled.get_count([1, 7]), Some(2));
+        assert_eq!(doubled.find([8]).unwrap().value, Some(12));
+        assert_eq!(doubled.iter().count(), t.iter().count());
+        assert_eq!(doubled.total_inserts(), t.total_inserts());
+        assert_eq!(doubled.validate(), Ok(()));
+        assert_index_live(&doubled);
+        assert_eq!(t.get_count([1, 7]), Some(1));
+
+        let ratios = t.map_values(|c| f64::from(c) / 10.0);
+        assert_eq!(ratios.find([8, 6]).unwrap().value, Some(0.3));
+        assert_eq!(Tree::new().map_values(|c| c + 1), Tree::new());
+    }
+
+    #[test]
+    fn test_retain() {
+        let mut t = sample_apriori_tree();
+        t.retain(|key, _| key.len() >= 3);
+        assert_eq!(t.distinct_keys(), 8);
+        assert!(!t.contains([8, 2]) && !t.contains([1, 7]));
+        assert!(t.find([1]).is_none());
+        assert_eq!(t.find([8]).unwrap().value, Some(5));
+        assert_eq!(t.total_inserts(), 8);
+        assert_eq!(t.validate(), Ok(()));
+
+        let mut t = sample_tree();
+        t.retain(|key, _| key.len() != 2);
+        assert!(!t.contains([3, 137]));
+        assert_eq!(t.find([3, 137]).unwrap().value, Some(1));
+        assert_eq!(t.iter().collect::<Vec<_>>(), vec![(vec![3, 137, 137], 1), (vec![1, 2, 9], 1)]);
+        assert_eq!(t.validate(), Ok(()));
+
+        t.retain(|_, count| count > 1);
+        assert!(t.is_empty());
+    }
+
+    #[test]
+    fn test_update_with() {
+        let mut t = Tree::new();
+        t.append(vec![3, 137, 2]);
+        t.update_with(vec![3, 137], |n| {
+            assert_eq!(n, None);
+            7
+        });
+        assert_eq!(t.get_count([3, 137]), Some(8));
+        t.update_with(vec![3, 137], |n| {
+            assert_eq!(n, Some(7));
+            4
+        });
+        assert_eq!(t.get_count([3, 137]), Some(5));
+        assert_eq!(t.total_inserts(), 5);
+
+        t.update_with(vec![3, 137, 2], |n| n.unwrap_or(0) + 1);
+        assert_eq!(t.get_count([3, 137, 2]), Some(2));
+        t.update_with(vec![3, 137], |_| 0);
+        assert!(!t.contains([3, 137]));
+        t.update_with(vec![5], |_| 0);
+        assert!(!t.contains([5]));
+        assert_eq!(t.total_inserts(), 2);
+        assert_eq!(t.validate(), Ok(()));
+        assert_index_live(&t);
+    }
+
+    #[test]
+    fn test_subtree_sum() {
+        let t = sample_tree();
+        assert_eq!(t.subtree_sum([3]), Some(3));
+        assert_eq!(t.subtree_sum([3, 137, 137]), Some(1));
+        assert_eq!(t.subtree_sum([1, 2]), Some(1));
+        assert_eq!(t.subtree_sum([]), Some(4));
+        assert_eq!(t.subtree_sum([3, 2]), None);
+        assert_eq!(t.subtree_sum([4]), None);
+        assert_eq!(Tree::new().subtree_sum([]), None);
+
+        let t = sample_apriori_tree();
+        assert_eq!(t.subtree_sum([8, 6]), Some(3));
+        assert_eq!(t.subtree_sum([8]), Some(6));
+    }
+
+    #[test]
+    fn test_iter_depth_limited() {
+        let mut t = sample_apriori_tree();
+        let shallow: Vec<_> = t.iter_depth_limited(2).map(|(key, _)| key).collect();
+        assert_eq!(shallow, vec![vec![8, 2], vec![6, 2, 4, 7], vec![6, 8, 4, 1], vec![2, 8, 4, 0, 7], vec![1, 7]]);
+        assert_eq!(t.iter_depth_limited(1).count(), 2);
+        assert_eq!(t.iter_depth_limited(t.height()).collect::<Vec<_>>(), t.iter().collect::<Vec<_>>());
+        assert_eq!(t.iter_depth_limited(0).count(), 0);
+        t.append(vec![]);
+        assert_eq!(t.iter_depth_limited(0).collect::<Vec<_>>(), vec![(vec![], 11)]);
+    }
+
+    #[test]
+    fn test_top_k() {
+        let mut t = sample_apriori_tree();
+        assert_eq!(t.top_k(3), vec![(vec![1, 7], 1), (vec![2, 8, 4, 0, 7], 1), (vec![6, 2, 4, 7], 1)]);
+        t.append_with_count(vec![8, 6, 2, 0], 2);
+        t.append(vec![8, 2]);
+        t.append(vec![8]);
+        assert_eq!(t.top_k(4), vec![
+            (vec![8], 10), (vec![8, 6, 2, 0], 3), (vec![8, 2], 2), (vec![1, 7], 1),
+        ]);
+        assert_eq!(t.top_k(100).len(), 11);
+        assert_eq!(t.top_k(100)[10], (vec![8, 6, 5, 0, 3], 1));
+        assert!(t.top_k(0).is_empty());
+        assert!(Tree::new().top_k(3).is_empty());
+        t.append(Vec::new());
+        assert_eq!(t.top_k(1), vec![(vec![], 15)]);
+    }
+
+    #[test]
+    fn test_scale_counts() {
+        let t = sample_tree();
+        let mut scaled = t.clone();
+        scaled.scale_counts(2);
+        for (key, n) in t.iter() {
+            assert_eq!(scaled.get_count(&key), Some(2 * n));
+        }
+        assert_eq!(scaled.total_inserts(), 2 * t.total_inserts());
+        assert_eq!(scaled.validate(), Ok(()));
+        assert_index_live(&scaled);
+
+        scaled.scale_counts(u32::MAX);
+        assert_eq!(scaled.get_count([3, 137]), Some(u32::MAX));
+        assert_eq!(scaled.get_count([3, 137, 137]), Some(u32::MAX));
+
+        scaled.scale_counts(0);
+        assert!(scaled.iter().all(|(_, n)| n == 0));
+        assert_eq!(scaled.total_inserts(), 0);
+    }
+
+    #[test]
+    fn test_decay() {
+        let mut t = sample_apriori_tree();
+        t.decay(0.5);
+        assert_eq!(t.find([8]).unwrap().value, Some(3));
+        assert_eq!(t.find([8, 6]).unwrap().value, Some(1));
+        assert_eq!(t.find([8, 6, 2]).unwrap().value, Some(1));
+        assert_eq!(t.get_count([8, 5, 1, 3]), Some(0));
+        assert_eq!(t.total_inserts(), 5);
+        assert_eq!(t.validate(), Ok(()));
+
+        t.append_with_count([1, 7], 4);
+        assert_eq!(t.get_count([1, 7]), Some(4));
+        t.prune_below(1);
+        assert_eq!(t.iter().collect::<Vec<_>>(), vec![(vec![1, 7], 4)]);
+        assert!(t.find([8]).is_none());
+        assert_eq!(t.validate(), Ok(()));
+
+        t.decay(0.0);
+        t.prune_below(1);
+        assert_eq!(t.node_count(), 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "not between 0 and 1")]
+    fn test_decay_factor() {
+        sample_tree().decay(1.5);
+    }
+
+    #[test]
+    fn test_prune_below() {
+        // nodes left without keys go as well
+        let mut t = sample_apriori_tree();
+        t.prune_below(3);
+        assert!(t.is_empty());
+        assert_eq!(t.node_count(), 0);
+        assert_eq!(t.total_inserts(), 0);
+        assert!(t.nodeindex.is_empty());
+        assert_eq!(t.validate(), Ok(()));
+
+        let mut t = sample_apriori_tree();
+        t.append_with_count([8, 6], 2);
+        t.append([8, 6, 2]);
+        t.prune_below(3);
+        let mut nodes = Vec::new();
+        t.walk(|path, node| nodes.push((path.to_vec(), node.value, node.is_terminal())));
+        assert_eq!(nodes, vec![(vec![8], Some(3), false), (vec![8, 6], Some(3), true), (vec![8, 6, 2], Some(1), true)]);
+        assert_eq!(t.iter().collect::<Vec<_>>(), vec![(vec![8, 6], 3), (vec![8, 6, 2], 1)]);
+        assert_eq!(t.total_inserts(), 3);
+        assert_eq!(t.validate(), Ok(()));
+
+        let mut t = sample_tree();
+        t.prune_below(2);
+        assert_eq!(t.iter().collect::<Vec<_>>(), vec![(vec![3, 137], 1)]);
+        assert_eq!(t.entry([3, 137]).or_insert(0), 1);
+        assert_eq!(t.total_inserts(), 1);
+        t.prune_below(2);
+        assert!(t.is_empty());
+        assert_eq!(t.validate(), Ok(()));
+    }
+
+    #[test]
+    fn test_prefix_count() {
+        let t = sample_tree();
+        assert_eq!(t.prefix_count([3]), 2);
+        assert_eq!(t.prefix_count([3, 137]), 2);
+        assert_eq!(t.prefix_count([3, 137, 137]), 1);
+        assert_eq!(t.prefix_count([1]), 1);
+        assert_eq!(t.prefix_count([3, 2]), 0);
+        assert_eq!(t.prefix_count([]), 3);
+        let t = sample_apriori_tree();
+        for prefix in &[vec![8], vec![8, 6], vec![6, 2], vec![1, 7]] {
+            assert_eq!(t.prefix_count(prefix), t.with_prefix(prefix).len());
+        }
+    }
+
+    #[test]
+    fn test_find_within_distance() {
+        let t = sample_tree();
+        assert_eq!(t.find_within_distance([3, 137], 0), vec![(vec![3, 137], 2)]);
+        assert_eq!(t.find_within_distance([3, 137], 1), vec![(vec![3, 137], 2), (vec![3, 137, 137], 1)]);
+        assert_eq!(t.find_within_distance([1, 5, 9], 1), vec![(vec![1, 2, 9], 1)]);
+        assert_eq!(t.find_within_distance([2, 9], 1), vec![(vec![1, 2, 9], 1)]);
+        assert_eq!(t.find_within_distance([3], 1), vec![(vec![3, 137], 2)]);
+        assert!(t.find_within_distance([3], 0).is_empty());
+        assert!(t.find_within_distance([7, 7, 7], 2).is_empty());
+
+        assert!(t.find_within_distance([], 1).is_empty());
+        assert_eq!(t.find_within_distance([], 2), vec![(vec![3, 137], 2)]);
+        assert_eq!(t.find_within_distance([], 3).len(), 3);
+    }
+
+    #[test]
+    fn test_find_wildcard() {
+        let t = sample_tree();
+        assert_eq!(t.find_wildcard([Some(3), None]), vec![(vec![3, 137], 2)]);
+        assert_eq!(t.find_wildcard([None, None]), vec![(vec![3, 137], 2)]);
+        assert_eq!(t.find_wildcard([None, Some(137), None]), vec![(vec![3, 137, 137], 1)]);
+        assert_eq!(t.find_wildcard([None, None, None]), vec![(vec![3, 137, 137], 1), (vec![1, 2, 9], 1)]);
+        assert_eq!(t.find_wildcard([Some(1), None, Some(9)]), vec![(vec![1, 2, 9], 1)]);
+        assert!(t.find_wildcard([Some(1), None]).is_empty());
+        assert!(t.find_wildcard([None]).is_empty());
+        assert!(t.find_wildcard([None, None, None, None]).is_empty());
+        assert!(t.find_wildcard(Vec::new()).is_empty());
+    }
+
+    #[test]
+    fn test_min_max_key() {
+        let t = sample_tree();
+        assert_eq!(t.min_key(), Some(vec![1, 2, 9]));
+        assert_eq!(t.max_key(), Some(vec![3, 137, 137]));
+        let t = sample_apriori_tree();
+        assert_eq!(t.min_key(), Some(vec![1, 7]));
+        assert_eq!(t.max_key(), Some(vec![8, 6, 5, 0, 3]));
+
+        let mut t = Tree::new();
+        assert_eq!(t.min_key(), None);
+        assert_eq!(t.max_key(), None);
+        t.append(vec![5, 1]);
+        t.append(vec![5]);
+        t.append(vec![5, 0, 9]);
+        assert_eq!(t.min_key(), Some(vec![5]));
+        assert_eq!(t.max_key(), Some(vec![5, 1]));
+    }
+
+    #[test]
+    fn test_iter() {
+        assert_eq!(Tree::new().iter().count(), 0);
+        assert_eq!(sample_tree().iter().collect::<Vec<_>>(), vec![
+            (vec![3u32, 137u32], 2),
+            (vec![3u32, 137u32, 137u32], 1),
+            (vec![1u32, 2u32, 9u32], 1),
+        ]);
+        let mut t = Tree::new();
+        t.append(vec![3u32, 137u32, 2u32]);
+        t.append(vec![3u32, 137u32, 99u32, 22u32]);
+        assert_eq!(t.iter().collect::<Vec<_>>(), vec![
+            (vec![3u32, 137u32, 2u32], 1),
+            (vec![3u32, 137u32, 99u32, 22u32], 1),
+        ]);
+    }
+
+    #[test]
+    fn test_iter_sorted() {
+        let t = sample_tree();
+        assert_eq!(t.iter_sorted().collect::<Vec<_>>(),
+                   vec![(vec![1, 2, 9], 1), (vec![3, 137], 2), (vec![3, 137, 137], 1)]);
+
+        let t = sample_apriori_tree();
+        let keys: Vec<Vec<u32>> = t.iter_sorted().map(|(k, _)| k).collect();
+        assert_eq!(keys, vec![
+            vec![1, 7], vec![2, 8, 4, 0, 7], vec![6, 2, 4, 7], vec![6, 8, 4, 1],
+            vec![8, 2], vec![8, 5, 0], vec![8, 5, 1, 3], vec![8, 6, 2, 0],
+            vec![8, 6, 2, 5, 4, 1], vec![8, 6, 5, 0, 3],
+        ]);
+        let mut reversed = Tree::new();
+        for key in keys.iter().rev() {
+            reversed.append(key);
         }
+        assert!(reversed.iter_sorted().map(|(k, _)| k).eq(keys));
+        assert_eq!(Tree::new().iter_sorted().count(), 0);
     }
 
-    fn common_prefix<K: AsRef<[u32]>>(&self, other: K) -> usize {
-        self.key.iter()
-            .zip(other.as_ref().into_iter())
-            .take_while(|&(a, b)| a == b)
-            .count()
+    #[test]
+    fn test_walk() {
+        let mut seen = Vec::new();
+        sample_tree().walk(|path, node| seen.push((path.to_vec(), node.key.clone())));
+        assert_eq!(seen, vec![
+            (vec![3u32, 137u32], vec![3u32, 137u32]),
+            (vec![3u32, 137u32, 137u32], vec![137u32]),
+            (vec![1u32, 2u32, 9u32], vec![1u32, 2u32, 9u32]),
+        ]);
+        let mut nodes = 0;
+        sample_apriori_tree().walk(|_, _| nodes += 1);
+        assert_eq!(nodes, 15);
     }
 
-    pub fn find<K: AsRef<[u32]>>(&self, key: K) -> Option<&Node<T>> {
-        let key = key.as_ref();
-        let prefix = self.common_prefix(key);
-        if prefix == 0 {
-            self.sibling.as_ref().and_then(|x| x.find(key))
-        } else if prefix == self.key.len() {
-            if prefix == key.len() {
-                Some(self)
-            } else {
-                self.child.as_ref().and_then(|x| x.find(&key[prefix..]))
-            }
-        } else {
-            None
-        }
+    #[test]
+    fn test_bfs() {
+        assert_eq!(Tree::new().bfs().count(), 0);
+        let keys: Vec<_> = sample_apriori_tree().bfs().map(|n| n.key.clone()).collect();
+        assert_eq!(keys, vec![
+            vec![8], vec![6], vec![2, 8, 4, 0, 7], vec![1, 7],
+            vec![5], vec![6], vec![2], vec![2, 4, 7], vec![8, 4, 1],
+            vec![1, 3], vec![0], vec![2], vec![5, 0, 3],
+            vec![5, 4, 1], vec![0],
+        ]);
     }
 
-    fn count_key_len(&self) -> (usize, usize) {
-        let mut no_nodes = 1;
-        let mut no_key_el = self.key.len();
+    #[test]
+    fn test_children() {
+        let t = sample_apriori_tree();
+        let top: Vec<_> = t.root.children().map(|n| n.key.clone()).collect();
+        assert_eq!(top, vec![vec![8], vec![6], vec![2, 8, 4, 0, 7], vec![1, 7]]);
+        let root = t.find(vec![8]).unwrap();
+        let keys: Vec<_> = root.children().map(|n| n.key.clone()).collect();
+        assert_eq!(keys, vec![vec![5], vec![6], vec![2]]);
+        let leaf = t.find(vec![8, 5, 1, 3]).unwrap();
+        assert_eq!(leaf.children().count(), 0);
+        assert_eq!(leaf.key(), &[1, 3]);
+        let keys: Vec<_> = t.find(vec![8, 6]).unwrap().children().map(|n| n.key()).collect();
+        assert_eq!(keys, vec![&[2][..], &[5, 0, 3][..]]);
+    }
 
-        if self.sibling.is_some() {
-            let c = self.sibling.as_ref().unwrap().count_key_len();
-            no_nodes += c.0;
-            no_key_el += c.1;
+    #[test]
+    fn test_remove() {
+        let mut t = sample_tree();
+        t.append(vec![1u32, 2u32, 9u32]);
+        assert_eq!(t.remove(vec![1u32, 2u32, 9u32]), Some(2));
+        assert!(!t.contains(vec![1u32, 2u32, 9u32]));
+        assert_eq!(t.node_count(), 2);
+        assert_eq!(t.total_inserts(), 2);
+        assert_eq!(t.remove(vec![3u32, 137u32, 137u32]), Some(1));
+        assert_eq!(t.iter().collect::<Vec<_>>(), vec![(vec![3u32, 137u32], 1)]);
+        assert_eq!(t.remove(vec![3u32, 137u32]), Some(1));
+        assert!(t.is_empty());
+    }
+
+    #[test]
+    fn test_remove_prefix_key() {
+        let mut t = sample_tree();
+        assert_eq!(t.remove(vec![3u32, 137u32]), Some(1));
+        assert!(!t.contains(vec![3u32, 137u32]));
+        assert_eq!(t.get_count(vec![3u32, 137u32, 137u32]), Some(1));
+        assert_eq!(t.find(vec![3u32, 137u32]).unwrap().value, Some(1));
+        assert_eq!(t.node_count(), 3);
+    }
+
+    #[test]
+    fn test_remove_missing() {
+        let mut t = sample_tree();
+        assert_eq!(t.remove(vec![3u32]), None);
+        assert_eq!(t.remove(vec![999u32]), None);
+        assert_eq!(t.remove(vec![3u32, 137u32, 137u32, 137u32]), None);
+        assert_eq!(t.iter().collect::<Vec<_>>(), sample_tree().iter().collect::<Vec<_>>());
+        let mut t = Tree::new();
+        t.append(vec![3u32, 137u32, 2u32]);
+        t.append(vec![3u32, 137u32, 99u32]);
+        assert_eq!(t.remove(vec![3u32, 137u32]), None);
+        assert_eq!(t.total_inserts(), 2);
+    }
+
+    #[test]
+    fn test_decrement() {
+        let mut t = sample_tree();
+        assert!(t.decrement(vec![3u32, 137u32, 137u32]));
+        assert_eq!(t.get_count(vec![3u32, 137u32]), Some(1));
+        assert_eq!(t.get_count(vec![3u32, 137u32, 137u32]), Some(0));
+        assert_eq!(t.node_count(), 3);
+        assert!(!t.decrement(vec![3u32, 137u32, 137u32]));
+        assert_eq!(t.get_count(vec![3u32, 137u32]), Some(1));
+        assert_eq!(t.get_count(vec![3u32, 137u32, 137u32]), Some(0));
+        assert!(t.decrement(vec![3u32, 137u32]));
+        assert_eq!(t.get_count(vec![3u32, 137u32]), Some(0));
+        t.append(vec![3u32, 137u32, 137u32]);
+        assert_eq!(t.get_count(vec![3u32, 137u32]), Some(1));
+        assert_eq!(t.get_count(vec![3u32, 137u32, 137u32]), Some(1));
+    }
+
+    #[test]
+    fn test_decrement_keeps_longer_keys() {
+        let mut t = Tree::new();
+        t.append(vec![1]);
+        t.append(vec![1, 2]);
+        assert!(t.decrement([1]));
+        assert!(!t.decrement([1]));
+        assert_eq!(t.get_count([1]), Some(1));
+        assert_eq!(t.total_inserts(), 1);
+        assert_eq!(t.validate(), Ok(()));
+        t.prune_below(1);
+        assert!(t.contains([1, 2]));
+        assert_eq!(t.remove([1, 2]), Some(1));
+        assert_eq!(t.validate(), Ok(()));
+    }
+
+    #[test]
+    fn test_decrement_missing() {
+        let mut t = sample_tree();
+        assert!(!t.decrement(vec![3u32]));
+        assert!(!t.decrement(vec![999u32]));
+        assert!(!Tree::new().decrement(vec![3u32]));
+        assert_eq!(t.iter().collect::<Vec<_>>(), sample_tree().iter().collect::<Vec<_>>());
+        assert_eq!(t.total_inserts(), 3);
+    }
+
+    #[test]
+    fn test_append_with_count() {
+        let mut t = Tree::new();
+        t.append_with_count(vec![3u32, 137u32], 3);
+        t.append_with_count(vec![3u32, 137u32, 2u32], 2);
+        t.append_with_count(vec![1u32], 0);
+        let mut u = Tree::new();
+        for _ in 0..3 {
+            u.append(vec![3u32, 137u32]);
+        }
+        for _ in 0..2 {
+            u.append(vec![3u32, 137u32, 2u32]);
         }
+        assert_eq!(t.iter().collect::<Vec<_>>(), u.iter().collect::<Vec<_>>());
+        assert_eq!(t.get_count(vec![3u32, 137u32]), Some(5));
+        assert_eq!(t.total_inserts(), 5);
+        assert!(!t.contains(vec![1u32]));
+    }
 
-        if self.child.is_some() {
-            let c = self.child.as_ref().unwrap().count_key_len();
-            no_nodes += c.0;
-            no_key_el += c.1;
+    #[test]
+    fn test_append_all() {
+        let mut t = Tree::new();
+        let n = t.append_all(vec![vec![3u32, 137u32], vec![3u32, 137u32, 137u32], vec![1u32, 2u32, 9u32]]);
+        assert_eq!(n, 3);
+        assert_eq!(t.iter().collect::<Vec<_>>(), sample_tree().iter().collect::<Vec<_>>());
+        assert_eq!(t.append_all(Vec::<Vec<u32>>::new()), 0);
+        assert_eq!(t.total_inserts(), 3);
+    }
+
+    #[test]
+    fn test_shrink_to_fit() {
+        let mut t = Tree::new();
+        t.reserve(1000);
+        assert!(t.nodeindex.capacity() >= 1000);
+        for i in 0..1000 {
+            t.append(vec![i, i + 1]);
         }
+        t.retain(|key, _| key[0] < 10);
+        let before: Vec<_> = t.iter().collect();
+        t.shrink_to_fit();
+        assert!(t.nodeindex.capacity() < 1000);
+        assert_eq!(t.iter().collect::<Vec<_>>(), before);
+        assert_index_live(&t);
+    }
 
-        return (no_nodes, no_key_el)
+    #[test]
+    fn test_to_hashmap() {
+        let t = sample_apriori_tree();
+        let map = t.to_hashmap();
+        assert_eq!(map.len(), 10);
+        assert_eq!(map[&vec![8, 5, 1, 3]], 1);
+        assert_eq!(map[&vec![8, 2]], 1);
+        assert!(!map.contains_key(&vec![8]));
+
+        let mut t = sample_tree();
+        t.append(vec![]);
+        let map = t.to_hashmap();
+        assert_eq!(map.len(), 4);
+        assert_eq!(map[&vec![3, 137]], 2);
+        assert_eq!(map[&vec![]], 4);
+        assert!(Tree::new().to_hashmap().is_empty());
     }
-}
 
-enum AppendType {
-    SameNode,
-    NewStraightChild,
-    NewGayChild,
-    NewSibling,
-}
+    #[test]
+    fn test_from_pairs() {
+        let mut t = sample_tree();
+        t.append(vec![]);
+        t.append(vec![3]);
+        let u = Tree::from_pairs(t.to_hashmap());
+        assert_eq!(u, t);
+        assert_eq!(u.fingerprint(), t.fingerprint());
+        assert_eq!(u.total_inserts(), t.total_inserts());
+        assert_eq!(u.validate(), Ok(()));
 
-impl Node<u32> {
-    fn boxed<K: Into<Vec<u32>>>(key: K, value: u32, tree: *mut Tree) -> Rc<Node<u32>> {
-        let n = Rc::new(Self::new(key, value, tree));
-        unsafe { (*tree).index_node(&n) };
-        n
+        let t = sample_apriori_tree();
+        assert_eq!(Tree::from_pairs(t.to_hashmap()), t);
+
+        let u = Tree::from_pairs(vec![(vec![1], 2), (vec![1, 2], 3)]);
+        assert!(!u.contains([1]));
+        assert_eq!(u.get_count([1, 2]), Some(3));
+        assert!(Tree::from_pairs(Vec::new()).is_empty());
     }
 
-    pub fn append<K: AsRef<[u32]>>(&self, key: K) -> Node<u32> {
-        let key = key.as_ref();
-        let prefix = self.common_prefix(key);
-        let state;
-        if prefix == 0 {
-            state = AppendType::NewSibling;
-        } else if prefix < key.len() {
-            if prefix < self.key.len() {
-                state = AppendType::NewGayChild;
-            } else {
-                state = AppendType::NewStraightChild;
-            }
-        } else {
-            state = AppendType::SameNode;
+    #[test]
+    fn test_count_histogram() {
+        let t = sample_apriori_tree();
+        let histogram = t.count_histogram();
+        assert_eq!(histogram.values().sum::<usize>(), t.node_count());
+        let leaves = t.bfs().filter(|x| x.is_leaf()).count();
+        assert_eq!(histogram[&1], leaves);
+        assert_eq!(histogram[&6], 1);
+        assert_eq!(histogram.keys().next_back(), Some(&6));
+        assert!(Tree::new().count_histogram().is_empty());
+    }
+
+    #[test]
+    fn test_successor_predecessor() {
+        let mut t = sample_apriori_tree();
+        t.append(vec![8, 6]);
+        let keys: Vec<Vec<u32>> = t.iter_sorted().map(|(key, _)| key).collect();
+        for pair in keys.windows(2) {
+            assert_eq!(t.successor(&pair[0]).as_ref(), Some(&pair[1]));
+            assert_eq!(t.predecessor(&pair[1]).as_ref(), Some(&pair[0]));
         }
+        assert_eq!(t.successor(keys.last().unwrap()), None);
+        assert_eq!(t.predecessor(&keys[0]), None);
 
-        Node {
-            key: match state {
-                AppendType::NewGayChild => self.key[0..prefix].to_vec(),
-                _ => self.key.clone(),
-            },
-            value: match state {
-                AppendType::NewSibling => self.value.clone(),
-                _ => Some(self.value.unwrap() + 1u32),
-            },
-            child: match state {
-                AppendType::NewGayChild => Some(Rc::new(Node {
-                    key: self.key[prefix..].to_vec(),
-                    value: self.value.clone(),
-                    child: self.child.clone(),
-                    sibling: None,
-                    next: None,
-                    tree: self.tree,
-                }.append(&key[prefix..]))),
-                AppendType::NewStraightChild => match self.child {
-                    Some(ref child) => Some(Rc::new(child.append(&key[prefix..]))),
-                    _ => Some(Self::boxed(&key[prefix..], 1u32, self.tree)),
-                },
-                _ => self.child.clone(),
-            },
-            sibling: match prefix {
-                0 => match self.sibling {
-                    Some(ref sibling) => Some(Rc::new(sibling.append(key))),
-                    _ => Some(Self::boxed(key, 1u32, self.tree)),
-                },
-                _ => self.sibling.clone(),
-            },
-            next: None,
-            tree: self.tree,
+        // keys which were never appended, checked against the sorted keys
+        let mut probes = vec![vec![], vec![0], vec![9], vec![8, 6, 2, 5, 4, 1, 0], vec![8, 5, 1, 2]];
+        for key in &keys {
+            for i in 0..key.len() {
+                probes.push(key[..i].to_vec());
+                let mut probe = key[..=i].to_vec();
+                probe[i] += 1;
+                probes.push(probe.clone());
+                if probe[i] >= 2 {
+                    probe[i] -= 2;
+                    probes.push(probe);
+                }
+            }
         }
+        t.append(vec![]);
+        let keys: Vec<Vec<u32>> = t.iter_sorted().map(|(key, _)| key).collect();
+        for probe in &probes {
+            assert_eq!(t.successor(probe).as_ref(), keys.iter().find(|k| *k > probe), "after {:?}", probe);
+            assert_eq!(t.predecessor(probe).as_ref(), keys.iter().rev().find(|k| *k < probe), "before {:?}", probe);
+        }
+        assert_eq!(Tree::new().successor([1]), None);
+        assert_eq!(Tree::new().predecessor([1]), None);
     }
-}
 
-#[derive(Debug)]
-pub struct Tree {
-    root: Option<Rc<Node<u32>>>,
-    nodeindex: HashMap<u32, HashSet<Rc<Node<u32>>>>,
-}
+    #[test]
+    fn test_ordered_keys_skip_pruned_nodes() {
+        let mut t = Tree::new();
+        t.append_with_count(vec![1], 5);
+        t.append(vec![9, 1]);
+        t.append(vec![9, 2]);
+        t.prune_below(2);
+        assert!(t.find([9]).is_none());
+        assert_eq!(t.max_key(), Some(vec![1]));
+        assert_eq!(t.predecessor([9, 5]), Some(vec![1]));
+        assert_eq!(t.successor([1]), None);
+        assert_eq!(t.subtree_sum([9]), None);
 
-impl Tree {
-    pub fn new() -> Tree {
-        Tree {
-            root: None,
-            nodeindex: HashMap::new(),
+        let mut t = Tree::new();
+        t.append_with_count(vec![5], 5);
+        t.append(vec![0, 1]);
+        t.append(vec![0, 2]);
+        t.prune_below(2);
+        assert_eq!(t.min_key(), Some(vec![5]));
+        assert_eq!(t.successor([]), Some(vec![5]));
+        assert_eq!(t.predecessor([5]), None);
+
+        let mut t = sample_apriori_tree();
+        t.append_with_count(vec![8, 6, 2, 5], 3);
+        t.append_with_count(vec![8, 1], 4);
+        t.append_with_count(vec![3, 3], 4);
+        t.prune_below(3);
+        let keys: Vec<Vec<u32>> = t.iter_sorted().map(|(key, _)| key).collect();
+        assert_eq!(keys, vec![vec![3, 3], vec![8, 1], vec![8, 6, 2, 5]]);
+        assert_eq!(t.min_key().as_ref(), keys.first());
+        assert_eq!(t.max_key().as_ref(), keys.last());
+        let probes = vec![vec![], vec![2], vec![3, 3], vec![8], vec![8, 5], vec![8, 6], vec![8, 6, 2], vec![8, 6, 9], vec![9]];
+        for probe in &probes {
+            assert_eq!(t.successor(probe).as_ref(), keys.iter().find(|k| *k > probe), "after {:?}", probe);
+            assert_eq!(t.predecessor(probe).as_ref(), keys.iter().rev().find(|k| *k < probe), "before {:?}", probe);
         }
     }
 
-    pub fn index_node(&mut self, node: &Rc<Node<u32>>) {
-        let ref key = node.key;
-        for k in key {
-            let nodeindex = &mut self.nodeindex;
-            if !nodeindex.contains_key(k) {
-                nodeindex.insert(k.clone(), HashSet::new());
-            }
-            nodeindex.get_mut(k).unwrap().insert(Rc::clone(node));
+    #[test]
+    fn test_range() {
+        let mut t = sample_apriori_tree();
+        t.append(vec![8, 6]);
+        t.append(vec![]);
+        let keys: Vec<_> = t.iter_sorted().collect();
+        let bounds = vec![
+            (vec![], vec![9]),
+            (vec![8], vec![8, 6, 2, 5]),
+            (vec![8, 6], vec![8, 6, 5]),
+            (vec![2, 8], vec![8, 5, 0]),
+            (vec![1], vec![1, 7]),
+            (vec![3], vec![4]),
+            (vec![8, 6, 5], vec![8, 6]),
+            (vec![8, 6], vec![8, 6]),
+        ];
+        for (start, end) in bounds {
+            let expected: Vec<_> = keys.iter().filter(|(key, _)| *key >= start && *key < end).cloned().collect();
+            assert_eq!(t.range(&start, &end).collect::<Vec<_>>(), expected, "{:?}..{:?}", start, end);
         }
+        assert_eq!(t.range(vec![8, 6], vec![8, 6, 5]).map(|(key, _)| key).collect::<Vec<_>>(),
+                   vec![vec![8, 6], vec![8, 6, 2, 0], vec![8, 6, 2, 5, 4, 1]]);
+        assert_eq!(t.range(vec![], vec![1]).collect::<Vec<_>>(), vec![(vec![], 12)]);
     }
 
-    pub fn find<K: AsRef<[u32]>>(&self, key: K) -> Option<&Node<u32>> {
-        self.root.as_ref().and_then(|x| x.find(key))
+    #[test]
+    fn test_intersect_keys() {
+        let t = sample_tree();
+        let mut u = Tree::new();
+        u.append(vec![1, 2, 9]);
+        u.append(vec![3]);
+        u.append(vec![3, 137, 137, 1]);
+        assert_eq!(t.intersect_keys(&u), vec![vec![1, 2, 9]]);
+        assert_eq!(u.intersect_keys(&t), vec![vec![1, 2, 9]]);
+        assert_eq!(t.intersect_keys(&t), t.iter().map(|(key, _)| key).collect::<Vec<_>>());
+        assert!(t.intersect_keys(&Tree::new()).is_empty());
     }
 
-    pub fn append<K: AsRef<[u32]>>(&mut self, key: K) {
-        self.root = match self.root {
-            Some(ref root) => Some(Rc::new(root.append(key))),
-            _ => Some(Node::boxed(key.as_ref(), 1u32, self)),
-        }
+    #[test]
+    fn test_difference_keys() {
+        let t = sample_tree();
+        let keys: Vec<_> = t.iter().map(|(key, _)| key).collect();
+        assert_eq!(t.difference_keys(&Tree::new()), keys);
+        assert!(t.difference_keys(&t.clone()).is_empty());
+
+        let mut u = Tree::new();
+        u.append(vec![3, 137]);
+        u.append(vec![3]);
+        assert_eq!(t.difference_keys(&u), vec![vec![3, 137, 137], vec![1, 2, 9]]);
+        assert_eq!(u.difference_keys(&t), vec![vec![3]]);
     }
 
-    pub fn average_key_len(&self) -> (f64) {
-        let c = self.root.as_ref().unwrap().count_key_len();
-        c.1 as f64/c.0 as f64
+    #[test]
+    fn test_remove_prefix() {
+        let mut t = sample_tree();
+        assert_eq!(t.remove_prefix([3]), 2);
+        assert_eq!(t.iter().collect::<Vec<_>>(), vec![(vec![1, 2, 9], 1)]);
+        assert_eq!(t.total_inserts(), 1);
+        assert_eq!(t.validate(), Ok(()));
+        assert_index_live(&t);
+        assert_eq!(t.remove_prefix([3]), 0);
+        assert_eq!(t.remove_prefix([1, 3]), 0);
+
+        let mut t = sample_apriori_tree();
+        assert_eq!(t.remove_prefix([8, 6, 2, 5]), 1);
+        assert!(t.contains([8, 6, 2, 0]));
+        assert_eq!(t.find([8, 6]).unwrap().value, Some(2));
+        assert_eq!(t.find([8]).unwrap().value, Some(5));
+        assert_eq!(t.remove_prefix([8, 6]), 2);
+        assert_eq!(t.find([8]).unwrap().value, Some(3));
+        assert_eq!(t.total_inserts(), 7);
+        assert_eq!(t.validate(), Ok(()));
+        assert_index_live(&t);
+
+        assert_eq!(t.remove_prefix([8]), 3);
+        assert_eq!(t.remove_prefix([]), 4);
+        assert!(t.is_empty());
+        assert_eq!(t.total_inserts(), 0);
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::{Node, Tree};
-    use std::ptr;
-    use std::collections::HashSet;
+    #[test]
+    fn test_max_depth() {
+        let mut t = Tree::with_max_depth(3);
+        t.append(vec![8, 6, 2, 5, 4, 1]);
+        t.append(vec![8, 6, 2, 0]);
+        t.append(vec![8, 6]);
+        t.append_with_count(vec![1, 7, 3, 3], 2);
+        assert_eq!(t.get_count([8, 6, 2]), Some(2));
+        assert_eq!(t.get_count([8, 6]), Some(3));
+        assert_eq!(t.get_count([1, 7, 3]), Some(2));
+        assert!(!t.contains([8, 6, 2, 0]));
+        assert_eq!(t.height(), 2);
+        assert_eq!(t.distinct_keys(), 3);
+        assert_eq!(t.total_inserts(), 5);
+        assert_eq!(t.clone().max_depth, Some(3));
+
+        let mut t = Tree::with_max_depth(0);
+        t.append(vec![1, 2]);
+        assert_eq!(t.get_count([]), Some(1));
+        assert!(t.root.is_leaf());
+
+        let mut t = Tree::with_max_depth(1);
+        t.append(vec![1, 2, 3]);
+        assert_eq!(t.entry(vec![1, 2, 3]).or_insert(5), 1);
+        assert_eq!(t.entry(vec![1, 2]).key(), &[1]);
+        t.update_with([1, 4], |n| n.unwrap_or(0) + 2);
+        *t.get_mut([1, 5]).unwrap() += 1;
+        assert_eq!(t.get_count([1]), Some(4));
+        assert_eq!(t.total_inserts(), 4);
+        assert!(t.root.children().all(|x| x.is_leaf()));
+    }
 
     #[test]
-    fn test_common_prefix_empty() {
-        assert!(Node::new(vec![3u32, 137u32, 2u32], (), ptr::null_mut()).common_prefix([]) == 0);
+    fn test_get_node() {
+        let mut t = sample_apriori_tree();
+        for key in [vec![8], vec![8, 6, 2], vec![8, 6, 2, 5, 4, 1], vec![1, 7]] {
+            let node = t.get_node(&key).unwrap();
+            assert_eq!(node_addr(&node), node_addr(t.find(&key).unwrap()));
+        }
+        assert!(t.get_node([8, 6, 2, 5]).is_none());
+        assert!(t.get_node([9]).is_none());
+        assert!(t.get_node([]).is_none());
+
+        let node = t.get_node([8, 6]).unwrap();
+        t.append(vec![8, 6]);
+        assert_eq!(node.value, Some(3));
+        assert_eq!(t.find([8, 6]).unwrap().value, Some(4));
+        assert_eq!(t.validate(), Ok(()));
     }
 
     #[test]
-    fn test_common_prefix_short() {
-        assert!(Node::new(vec![3u32, 137u32, 2u32], (), ptr::null_mut()).common_prefix(vec![3u32, 137u32, 8u32, 2u32]) == 2);
+    fn test_compact() {
+        let mut t = Tree::new();
+        t.append(vec![1, 2, 3]);
+        t.append(vec![1, 2, 4, 5]);
+        t.append(vec![1, 2, 4, 6, 7]);
+        t.append(vec![1, 2, 4, 6, 8]);
+        t.append(vec![9]);
+        t.remove(vec![1, 2, 3]);
+        t.remove(vec![1, 2, 4, 6, 8]);
+        assert_eq!(t.node_count(), 6);
+        t.compact();
+
+        let mut expected = Tree::new();
+        expected.append(vec![1, 2, 4, 5]);
+        expected.append(vec![1, 2, 4, 6, 7]);
+        expected.append(vec![9]);
+        assert_eq!(t.to_dot(), expected.to_dot());
+        assert_eq!(t.node_count(), 4);
+        assert_eq!(t.validate(), Ok(()));
+        assert_index_live(&t);
+
+        // appended keys are kept even with a single child below them
+        t.append(vec![1, 2, 4]);
+        t.remove(vec![1, 2, 4, 5]);
+        t.compact();
+        assert!(t.contains([1, 2, 4]));
+        assert_eq!(t.get_count([1, 2, 4, 6, 7]), Some(1));
+        assert_eq!(t.node_count(), 3);
+        assert_index_live(&t);
+
+        // counts rounded down by decay leave the parent counting more
+        let mut t = Tree::new();
+        t.append(vec![1, 2]);
+        t.append(vec![1, 3]);
+        t.decay(0.5);
+        t.remove([1, 3]);
+        assert_eq!(t.find([1]).unwrap().value, Some(1));
+        t.compact();
+        assert_eq!(t.get_count([1, 2]), Some(1));
+        assert_eq!(t.validate(), Ok(()));
     }
 
     #[test]
-    fn test_find_empty() {
-        let t = Tree::new();
-        assert!(t.find([]).is_none());
-        assert!(t.find(vec![3u32, 137u32, 2u32]).is_none());
+    fn test_num_leaves_internal() {
+        let t = sample_apriori_tree();
+        assert_eq!(t.num_leaves() + t.num_internal(), t.node_count());
+        assert_eq!(t.num_leaves(), 10);
+        assert_eq!(t.num_internal(), 5);
+
+        let t = sample_tree();
+        assert_eq!(t.num_leaves(), 2);
+        assert_eq!(t.num_internal(), 1);
+        assert_eq!(Tree::new().num_leaves(), 0);
     }
 
-    fn sample_tree() -> Tree {
+    #[test]
+    fn test_memory_bytes() {
         let mut t = Tree::new();
+        let empty = t.memory_bytes();
+        assert!(empty >= mem::size_of::<Tree>());
+        let mut last = empty;
+        for i in 0..100 {
+            t.append(vec![i % 7, i % 11, i]);
+            let bytes = t.memory_bytes();
+            assert!(bytes > last);
+            last = bytes;
+        }
+        assert!(sample_apriori_tree().memory_bytes() > sample_tree().memory_bytes());
+        t.clear();
+        t.shrink_to_fit();
+        assert_eq!(t.memory_bytes(), empty);
+    }
+
+    #[test]
+    fn test_clear() {
+        let mut t = sample_apriori_tree();
+        t.clear();
+        assert!(t.is_empty());
+        assert!(t.nodeindex.is_empty());
+        assert_eq!(t.total_inserts(), 0);
+        assert_eq!(t.iter().count(), 0);
         t.append(vec![3u32, 137u32]);
         t.append(vec![3u32, 137u32, 137u32]);
         t.append(vec![1u32, 2u32, 9u32]);
-        t
+        assert_eq!(t.iter().collect::<Vec<_>>(), sample_tree().iter().collect::<Vec<_>>());
+        assert_eq!(t.nodeindex.len(), 5);
+        assert_eq!(t.total_inserts(), 3);
     }
 
     #[test]
-    fn test_sample_tree_nodeindex() {
-        let ref t = sample_tree();
-        let ref ni = t.nodeindex;
-        println!("node index: {:?}", ni);
-        assert!(ni.len() == 5);
-        let nodes_3 = ni.get(&3).unwrap();
-        assert!(nodes_3.len() == 1);
-        let n1 = t.find(vec![3u32, 137u32]).unwrap();
-        println!("n1: {:?}", n1);
-        // assert!(nodes_3.contains(&*n1));
-        // assert!(false);
+    fn test_merge() {
+        let mut t = Tree::new();
+        t.merge(&sample_apriori_tree());
+        assert_eq!(t.iter().collect::<Vec<_>>(), sample_apriori_tree().iter().collect::<Vec<_>>());
+        assert_eq!(t.total_inserts(), 10);
+
+        let mut t = sample_tree();
+        let mut u = Tree::new();
+        u.append(vec![3u32, 137u32]);
+        u.append(vec![3u32, 137u32]);
+        u.append(vec![3u32, 2u32]);
+        t.merge(&u);
+        assert_eq!(t.get_count(vec![3u32, 137u32]), Some(4));
+        assert_eq!(t.get_count(vec![3u32, 137u32, 137u32]), Some(1));
+        assert_eq!(t.get_count(vec![3u32, 2u32]), Some(1));
+        assert_eq!(t.get_count(vec![1u32, 2u32, 9u32]), Some(1));
+        assert_eq!(t.find(vec![3u32]).unwrap().value, Some(5));
+        assert_eq!(t.total_inserts(), 6);
     }
 
     #[test]
-    fn test_sample_tree_average_key_len() {
-        assert!(sample_tree().average_key_len() == 2.0);
+    fn test_merge_with() {
+        let mut t = Tree::new();
+        t.append_with_count(vec![1, 2], 3);
+        t.append_with_count(vec![1, 2, 3], 1);
+        t.append(vec![4]);
+        let mut u = Tree::new();
+        u.append_with_count(vec![1, 2], 2);
+        u.append_with_count(vec![1, 2, 3], 5);
+        u.append_with_count(vec![1], 2);
+
+        let mut max = t.clone();
+        max.merge_with(&u, |a, b| a.max(b));
+        assert_eq!(max.get_count([1, 2]), Some(8));
+        assert_eq!(max.get_count([1, 2, 3]), Some(5));
+        assert_eq!(max.get_count([1]), Some(10));
+        assert_eq!(max.get_count([4]), Some(1));
+        assert_eq!(max.total_inserts(), 11);
+        assert_eq!(max.validate(), Ok(()));
+
+        let mut min = t.clone();
+        min.merge_with(&u, |a, b| a.min(b));
+        assert_eq!(min.get_count([1, 2]), Some(3));
+        assert_eq!(min.get_count([1, 2, 3]), Some(1));
+        assert_eq!(min.get_count([1]), Some(5));
+
+        let mut sum = t.clone();
+        sum.merge_with(&u, |a, b| a + b);
+        t.merge(&u);
+        assert_eq!(sum, t);
+        assert_index_live(&sum);
     }
 
     #[test]
-    fn test_find_simple() {
-        assert!(sample_tree().find(vec![3u32, 137u32]).unwrap().value == Some(2));
+    fn test_clone() {
+        let mut t = sample_tree();
+        let c = t.clone();
+        t.append(vec![3u32, 137u32, 5u32]);
+        t.append(vec![7u32]);
+        assert_eq!(c.iter().collect::<Vec<_>>(), sample_tree().iter().collect::<Vec<_>>());
+        assert_eq!(c.total_inserts(), 3);
+        assert_eq!(t.get_count(vec![3u32, 137u32]), Some(3));
+        assert_eq!(c.nodeindex.len(), 5);
+        let t3 = t.nodeindex[&3].values().next().unwrap();
+        let c3 = c.nodeindex[&3].values().next().unwrap();
+        assert!(!Arc::ptr_eq(t3, c3));
+        assert!(Arc::ptr_eq(&c.root.children.nodes[0], c3));
     }
 
     #[test]
-    fn test_find_child() {
-        assert!(sample_tree().find(vec![3u32, 137u32, 137u32]).unwrap().value == Some(1));
+    fn test_from_iter() {
+        let t: Tree = vec![vec![3u32, 137u32], vec![3u32, 137u32, 137u32], vec![1u32, 2u32, 9u32]]
+            .into_iter()
+            .collect();
+        let shape = |t: &Tree| t.bfs().map(|n| (n.key.clone(), n.value, n.terminal)).collect::<Vec<_>>();
+        assert_eq!(shape(&t), shape(&sample_tree()));
+        assert_eq!(t.total_inserts(), 3);
     }
 
     #[test]
-    fn test_find_sibling() {
-        assert!(sample_tree().find(vec![1u32, 2u32, 9u32]).unwrap().value == Some(1));
+    fn test_extend() {
+        let mut t = sample_tree();
+        t.extend(vec![vec![3u32, 137u32], vec![5u32]]);
+        assert_eq!(t.get_count(vec![3u32, 137u32]), Some(3));
+        assert_eq!(t.get_count(vec![5u32]), Some(1));
+        assert_eq!(t.total_inserts(), 5);
     }
 
     #[test]
-    fn test_find_missing() {
-        assert!(sample_tree().find(vec![999u32]).is_none());
+    fn test_into_iter() {
+        let t = sample_apriori_tree();
+        let expected: Vec<_> = t.iter().collect();
+        assert_eq!(t.into_iter().collect::<Vec<_>>(), expected);
+        let t = sample_tree();
+        let c = t.clone();
+        assert_eq!((&t).into_iter().collect::<Vec<_>>(), c.into_iter().collect::<Vec<_>>());
+        assert_eq!(Tree::new().into_iter().count(), 0);
     }
 
     #[test]
-    fn test_find_shorter() {
-        assert!(sample_tree().find(vec![3u32]).is_none());
+    fn test_default() {
+        let t = Tree::default();
+        assert!(t.is_empty());
+        assert!(t.nodeindex.is_empty());
+        assert_eq!(t.total_inserts(), 0);
     }
 
-     #[test]
-    fn test_find_longer() {
-        assert!(sample_tree().find(vec![3u32, 137u32, 137u32, 137u32]).is_none());
+    #[test]
+    fn test_distinct_keys() {
+        assert_eq!(Tree::new().distinct_keys(), 0);
+        assert_eq!(sample_tree().distinct_keys(), 3);
+        assert_eq!(sample_apriori_tree().distinct_keys(), 10);
     }
 
     #[test]
-    fn test_insert_empty() {
-        let mut t = Tree::new();
-        t.append(vec![999u32]);
-        let root = t.root.as_ref().unwrap();
-        assert!(root.key == vec![999u32]);
-        assert!(root.value == Some(1));
-        assert!(root.child.is_none());
-        assert!(root.sibling.is_none());
+    fn test_append_weighted() {
+        let mut t = PrefixTree::<u32, f64>::new();
+        t.append_weighted(vec![3u32, 137u32], 0.5);
+        t.append_weighted(vec![3u32, 137u32, 2u32], 1.25);
+        assert!(t.find(vec![3u32, 137u32]).unwrap().value == Some(1.75));
+        assert!(t.find(vec![3u32, 137u32, 2u32]).unwrap().value == Some(1.25));
+        t.append(vec![3u32, 137u32, 2u32]);
+        t.append_weighted(vec![3u32, 4u32], 0.125);
+        assert!(t.find(vec![3u32]).unwrap().value == Some(2.875));
+        assert!(t.find(vec![3u32, 137u32, 2u32]).unwrap().value == Some(2.25));
+        assert_eq!(t.total_inserts(), 4);
     }
 
     #[test]
-    fn test_insert_append() {
+    fn test_append_wide_counts() {
+        let mut t: PrefixTree<u32, u64> = vec![vec![3u32, 137u32], vec![3u32]].into_iter().collect();
+        t.append_weighted(vec![3u32, 137u32], u64::from(u32::MAX));
+        t.extend(vec![vec![3u32, 137u32]]);
+        assert_eq!(t.get_count([3, 137]), Some(u64::from(u32::MAX) + 2));
+        assert_eq!(t.get_count([3]), Some(u64::from(u32::MAX) + 3));
+        assert_eq!(t.total_inserts(), 4);
+    }
+
+    #[test]
+    fn test_append_saturates() {
         let mut t = Tree::new();
-        t.append(vec![3u32]);
-        t.append(vec![3u32, 137u32]);
-        t.append(vec![3u32, 137u32, 2u32]);
-        let foo = t.root.as_ref().unwrap();
-        assert!(foo.key == vec![3u32]);
-        assert!(foo.value == Some(3));
-        assert!(foo.sibling.is_none());
-        let bar = foo.child.as_ref().unwrap();
-        assert!(bar.key == vec![137u32]);
-        assert!(bar.value == Some(2));
-        assert!(bar.sibling.is_none());
-        let baz = bar.child.as_ref().unwrap();
-        assert!(baz.key == vec![2u32]);
-        assert!(baz.value == Some(1));
-        assert!(baz.child.is_none());
-        assert!(baz.sibling.is_none());
+        t.append_with_count(vec![1, 2], u32::MAX - 1);
+        t.append(vec![1, 2]);
+        t.append(vec![1, 2]);
+        assert_eq!(t.get_count([1, 2]), Some(u32::MAX));
+        t.append_with_count(vec![1, 3], 5);
+        assert_eq!(t.find([1]).unwrap().value, Some(u32::MAX));
+        assert_eq!(t.get_count([1, 3]), Some(5));
+
+        let mut t = PrefixTree::<u8, u8>::new();
+        t.append_weighted([1u8], 200);
+        t.append_weighted([1u8], 200);
+        assert_eq!(t.get_count([1]), Some(255));
     }
 
     #[test]
-    fn test_insert_sibling() {
+    fn test_byte_keys() {
+        let mut t = PrefixTree::<u8, u32>::new();
+        t.append(b"hello");
+        t.append(&b"help"[..]);
+        assert!(t.find(b"hello").unwrap().value == Some(1));
+        assert!(t.find(b"help").unwrap().value == Some(1));
+        let root = t.root.children().next().unwrap();
+        assert!(root.key == b"hel".to_vec());
+        assert!(root.value == Some(2));
+    }
+
+    #[test]
+    fn test_fmt_display() {
+        assert_eq!(Tree::new().to_string(), "");
+        assert_eq!(sample_tree().to_string(), "\
+├─ [3, 137] (2)
+│  └─ [137] (1)
+└─ [1, 2, 9] (1)
+");
         let mut t = Tree::new();
-        t.append(vec![987u32]);
-        t.append(vec![654u32]);
-        t.append(vec![321u32]);
-        let foo = t.root.as_ref().unwrap();
-        assert!(foo.key == vec![987u32]);
-        assert!(foo.value == Some(1));
-        assert!(foo.child.is_none());
-        let bar = foo.sibling.as_ref().unwrap();
-        assert!(bar.key == vec![654u32]);
-        assert!(bar.value == Some(1));
-        assert!(bar.child.is_none());
-        let quux = bar.sibling.as_ref().unwrap();
-        assert!(quux.key == vec![321u32]);
-        assert!(quux.value == Some(1));
-        assert!(quux.child.is_none());
-        assert!(quux.sibling.is_none());
+        t.append(vec![3u32, 137u32, 2u32]);
+        t.append(vec![3u32, 137u32, 99u32, 22u32]);
+        assert_eq!(t.to_string(), "\
+└─ [3, 137] (2)
+   ├─ [2] (1)
+   └─ [99, 22] (1)
+");
     }
 
     #[test]
-    fn test_insert_split() {
+    fn test_to_dot() {
+        assert_eq!(Tree::new().to_dot(), "digraph {}\n");
         let mut t = Tree::new();
         t.append(vec![3u32, 137u32, 2u32]);
-        println!("test_insert_split/pre: {:?}", t);
         t.append(vec![3u32, 137u32, 99u32, 22u32]);
-        println!("test_insert_split/post: {:?}", t);
-        let root = t.root.as_ref().unwrap();
-        assert!(root.key == vec![3u32, 137u32]);
-        assert!(root.value == Some(2));
-        assert!(root.sibling.is_none());
-        let foo = root.child.as_ref().unwrap();
-        assert!(foo.key == vec![2u32]);
-        assert!(foo.value == Some(1));
-        assert!(foo.child.is_none());
-        let bar = foo.sibling.as_ref().unwrap();
-        assert!(bar.key == vec![99u32, 22u32]);
-        assert!(bar.value == Some(1));
-        assert!(bar.sibling.is_none());
-        assert!(bar.child.is_none());
+        assert_eq!(t.to_dot(), "\
+digraph {
+    n0 [label=\"[3, 137] (2)\"];
+    n1 [label=\"[2] (1)\"];
+    n0 -> n1;
+    n2 [label=\"[99, 22] (1)\"];
+    n0 -> n2;
+}
+");
+        let mut t = PrefixTree::<&str, u32>::new();
+        t.append(vec!["a\"b"]);
+        assert!(t.to_dot().contains(r#"n0 [label="[\"a\\\"b\"] (1)"];"#));
+        assert_eq!(sample_apriori_tree().to_dot().matches(" -> ").count(), 11);
+    }
+
+    #[test]
+    fn test_concurrent_find() {
+        let t = Arc::new(sample_apriori_tree());
+        let handles: Vec<_> = (0..4).map(|_| {
+            let t = Arc::clone(&t);
+            thread::spawn(move || {
+                for _ in 0..100 {
+                    assert_eq!(t.get_count(vec![8u32, 6u32, 2u32, 5u32, 4u32, 1u32]), Some(1));
+                    assert!(t.find(vec![8u32, 6u32]).is_some());
+                }
+            })
+        }).collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
     }
 
     #[test]
@@ -336,21 +3713,128 @@ mod tests {
         println!("{:?}", sample_tree());
     }
 
+    /// Times `find` under wide fan-out against scanning the children of
+    /// every node on the way, as the sibling list children were kept in
+    /// did. Run with `cargo test --release -- --ignored --nocapture`.
+    #[test]
+    #[ignore]
+    fn bench_wide_fanout_lookup() {
+        use std::time::Instant;
+
+        fn scan<'a>(mut node: &'a Node<u32, u32>, mut key: &[u32]) -> Option<&'a Node<u32, u32>> {
+            while let Some(first) = key.first() {
+                node = node.children().find(|x| x.key[0] == *first)?;
+                key = &key[node.key.len()..];
+            }
+            Some(node)
+        }
+
+        let keys: Vec<Vec<u32>> = (0..512).flat_map(|i| (0..512).map(move |j| vec![i, j])).collect();
+        let t: Tree = keys.iter().collect();
+        let start = Instant::now();
+        assert!(keys.iter().all(|key| t.find(key).is_some()));
+        let map = start.elapsed();
+        let start = Instant::now();
+        assert!(keys.iter().all(|key| scan(&t.root, key).is_some()));
+        let list = start.elapsed();
+        println!("{} lookups at fan-out 512: {:?} by map, {:?} by scan", keys.len(), map, list);
+        assert!(map < list);
+    }
+
     #[test]
     fn test_insert_twice() {
         let mut t = Tree::new();
         t.append(vec![3u32, 137u32, 2u32]);
         t.append(vec![3u32, 137u32, 2u32]);
-        let root = t.root.as_ref().unwrap();
-        assert!(root.key == vec![3u32, 137u32, 2u32]);
-        assert!(root.value == Some(2));
-        assert!(root.sibling.is_none());
+        let top = children(&t.root);
+        assert_eq!(top.len(), 1);
+        assert!(top[0].key == vec![3u32, 137u32, 2u32]);
+        assert!(top[0].value == Some(2));
+        assert_eq!(t.total_inserts(), 2);
+
+        let mut expected = Tree::new();
+        expected.append_with_count(vec![3u32, 137u32, 2u32], 2);
+        assert_eq!(t, expected);
+    }
+
+    #[test]
+    fn test_sort_children() {
+        let mut t = sample_apriori_tree();
+        let before: Vec<_> = t.iter().collect();
+        assert_ne!(before, t.iter_sorted().collect::<Vec<_>>());
+        t.sort_children();
+        assert_eq!(t.iter().collect::<Vec<_>>(), t.iter_sorted().collect::<Vec<_>>());
+        for (key, n) in before {
+            assert_eq!(t.get_count(&key), Some(n));
+        }
+        assert_eq!(t.find([8, 6, 2]).unwrap().value, Some(2));
+        assert!(t.find([8, 9]).is_none());
+        assert_eq!(t.validate(), Ok(()));
+        assert_index_live(&t);
+
+        t.append(vec![0]);
+        assert!(t.contains([0]));
+        assert_eq!(t.iter().last(), Some((vec![0], 1)));
+    }
+
+    #[test]
+    fn test_fingerprint() {
+        let t = sample_apriori_tree();
+        let mut reversed = Tree::new();
+        for (key, n) in t.own_counts().into_iter().rev() {
+            reversed.append_with_count(key, n);
+        }
+        assert_eq!(t.fingerprint(), reversed.fingerprint());
+        assert_eq!(t.fingerprint(), t.clone().fingerprint());
+        assert_ne!(t.fingerprint(), sample_tree().fingerprint());
+        reversed.append(vec![8, 2]);
+        assert_ne!(t.fingerprint(), reversed.fingerprint());
+        assert_eq!(Tree::new().fingerprint(), 0xcbf2_9ce4_8422_2325);
+        assert_ne!(Tree::new().fingerprint(), t.fingerprint());
+    }
+
+    #[test]
+    fn test_diff() {
+        let t = sample_tree();
+        let mut u = t.clone();
+        u.append(vec![3, 137, 137]);
+        assert_eq!(t.diff(&u), vec![(vec![3, 137, 137], -1)]);
+        assert_eq!(u.diff(&t), vec![(vec![3, 137, 137], 1)]);
+        assert!(t.diff(&t.clone()).is_empty());
+
+        u.remove(vec![1, 2, 9]);
+        u.append_with_count(vec![4], 3);
+        assert_eq!(t.diff(&u), vec![(vec![3, 137, 137], -1), (vec![1, 2, 9], 1), (vec![4], -3)]);
+        assert_eq!(t.diff(&Tree::new()), vec![(vec![3, 137], 1), (vec![3, 137, 137], 1), (vec![1, 2, 9], 1)]);
+    }
+
+    #[test]
+    fn test_eq() {
+        let t = sample_apriori_tree();
+        let mut reversed = Tree::new();
+        for (key, n) in t.own_counts().into_iter().rev() {
+            reversed.append_with_count(key, n);
+        }
+        assert_eq!(t, reversed);
+        assert_eq!(Tree::new(), Tree::new());
+        assert_ne!(t, Tree::new());
+
+        reversed.append(vec![8, 2]);
+        assert_ne!(t, reversed);
+        let mut extra = t.clone();
+        extra.append(vec![4]);
+        assert_ne!(t, extra);
+        assert_ne!(extra, t);
+    }
+
+    fn children(node: &Node<u32, u32>) -> Vec<&Node<u32, u32>> {
+        node.children().collect()
     }
 
     fn sample_apriori_tree() -> Tree {
         let mut t: Tree = Tree::new();
         // total counts are (ordered desc.) [all input vecs in this order]
-        // 8: 8 times, 6: 5 times, 2: 5 times, 9: 4 times, 5: 4 times,
+        // 8: 8 times, 6: 5 times, 2: 5 times, 5: 4 times,
         // 4: 4 times, 1: 4 times, 0: 4 times, 7: 3 times, 3: 2 times
         println!("NEW Apriori sample tree:\n{:?}", t);
         t.append(vec![8, 5, 1, 3]);
@@ -381,88 +3865,88 @@ mod tests {
         let t = sample_apriori_tree();
         println!("Apriori sample:\n{:?}", t);
 
-        let root = t.root.as_ref().unwrap();
-        assert_eq!(root.key, vec![8]);
-        assert_eq!(root.value, Some(6));
-        // child below
-        // sibling below
+        let top = children(&t.root);
+        assert_eq!(top.len(), 4);
 
-        let r_5 = root.child.as_ref().unwrap();
+        let r = top[0];
+        assert_eq!(r.key, vec![8]);
+        assert_eq!(r.value, Some(6));
+        let below_r = children(r);
+        assert_eq!(below_r.len(), 3);
+
+        let r_5 = below_r[0];
         assert_eq!(r_5.key, vec![5]);
         assert_eq!(r_5.value, Some(2));
-        // child, sibling below
+        let below_r_5 = children(r_5);
+        assert_eq!(below_r_5.len(), 2);
 
-        let r_5_1_3 = r_5.child.as_ref().unwrap();
+        let r_5_1_3 = below_r_5[0];
         assert_eq!(r_5_1_3.key, vec![1, 3]);
         assert_eq!(r_5_1_3.value, Some(1));
-        assert!(r_5_1_3.child.is_none());
-        // sibling below
+        assert!(r_5_1_3.children.is_empty());
 
-        let r_5_0 = r_5_1_3.sibling.as_ref().unwrap();
+        let r_5_0 = below_r_5[1];
         assert_eq!(r_5_0.key, vec![0]);
         assert_eq!(r_5_0.value, Some(1));
-        assert!(r_5_0.child.is_none());
-        assert!(r_5_0.sibling.is_none());
+        assert!(r_5_0.children.is_empty());
 
-        let r_6 = r_5.sibling.as_ref().unwrap();
+        let r_6 = below_r[1];
         assert_eq!(r_6.key, vec![6]);
         assert_eq!(r_6.value, Some(3));
-        // child below
-        // sibling below
+        let below_r_6 = children(r_6);
+        assert_eq!(below_r_6.len(), 2);
 
-        let r_6_2 = r_6.child.as_ref().unwrap();
+        let r_6_2 = below_r_6[0];
         assert_eq!(r_6_2.key, vec![2]);
         assert_eq!(r_6_2.value, Some(2));
-        // child below
-        // sibling below
+        let below_r_6_2 = children(r_6_2);
+        assert_eq!(below_r_6_2.len(), 2);
 
-        let r_6_2_5_4_1 = r_6_2.child.as_ref().unwrap();
+        let r_6_2_5_4_1 = below_r_6_2[0];
         assert_eq!(r_6_2_5_4_1.key, vec![5, 4, 1]);
         assert_eq!(r_6_2_5_4_1.value, Some(1));
-        assert!(r_6_2_5_4_1.child.is_none());
-        // sibling below
+        assert!(r_6_2_5_4_1.children.is_empty());
 
-        let r_6_2_0 = r_6_2_5_4_1.sibling.as_ref().unwrap();
+        let r_6_2_0 = below_r_6_2[1];
         assert_eq!(r_6_2_0.key, vec![0]);
         assert_eq!(r_6_2_0.value, Some(1));
-        assert!(r_6_2_0.child.is_none());
-        assert!(r_6_2_0.sibling.is_none());
+        assert!(r_6_2_0.children.is_empty());
+
+        let r_6_5_0_3 = below_r_6[1];
+        assert_eq!(r_6_5_0_3.key, vec![5, 0, 3]);
+        assert_eq!(r_6_5_0_3.value, Some(1));
+        assert!(r_6_5_0_3.children.is_empty());
 
-        let r_2 = r_6.sibling.as_ref().unwrap();
+        let r_2 = below_r[2];
         assert_eq!(r_2.key, vec![2]);
         assert_eq!(r_2.value, Some(1));
-        assert!(r_2.child.is_none());
-        assert!(r_2.sibling.is_none());
+        assert!(r_2.children.is_empty());
 
-        let rs_6 = root.sibling.as_ref().unwrap();
+        let rs_6 = top[1];
         assert_eq!(rs_6.key, vec![6]);
         assert_eq!(rs_6.value, Some(2));
-        // child below
-        // sibling below
+        let below_rs_6 = children(rs_6);
+        assert_eq!(below_rs_6.len(), 2);
 
-        let rs_6_2_4_7 = rs_6.child.as_ref().unwrap();
+        let rs_6_2_4_7 = below_rs_6[0];
         assert_eq!(rs_6_2_4_7.key, vec![2, 4, 7]);
         assert_eq!(rs_6_2_4_7.value, Some(1));
-        assert!(rs_6_2_4_7.child.is_none());
-        // sibling below
+        assert!(rs_6_2_4_7.children.is_empty());
 
-        let rs_6_8_4_1 = rs_6_2_4_7.sibling.as_ref().unwrap();
+        let rs_6_8_4_1 = below_rs_6[1];
         assert_eq!(rs_6_8_4_1.key, vec![8, 4, 1]);
         assert_eq!(rs_6_8_4_1.value, Some(1));
-        assert!(rs_6_8_4_1.child.is_none());
-        assert!(rs_6_8_4_1.sibling.is_none());
+        assert!(rs_6_8_4_1.children.is_empty());
 
-        let rs_2 = rs_6.sibling.as_ref().unwrap();
+        let rs_2 = top[2];
         assert_eq!(rs_2.key, vec![2, 8, 4, 0, 7]);
         assert_eq!(rs_2.value, Some(1));
-        assert!(rs_2.child.is_none());
-        // sibling below
+        assert!(rs_2.children.is_empty());
 
-        let rs_1 = rs_2.sibling.as_ref().unwrap();
+        let rs_1 = top[3];
         assert_eq!(rs_1.key, vec![1, 7]);
         assert_eq!(rs_1.value, Some(1));
-        assert!(rs_1.child.is_none());
-        assert!(rs_1.sibling.is_none());
+        assert!(rs_1.children.is_empty());
     }
 
 }
@@ -0,0 +1,519 @@
+//! Frequent itemset mining over counting trees.
+
+use prelude::*;
+use prelude::hash::Hash;
+use prelude::iter::FromIterator;
+
+use super::PrefixTree;
+
+/// An association rule: transactions containing the `antecedent` tend to
+/// contain the `consequent` too.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rule<K> {
+    pub antecedent: Vec<K>,
+    pub consequent: Vec<K>,
+    /// The number of transactions containing both sides.
+    pub support: u32,
+    /// The fraction of transactions containing the antecedent which
+    /// contain the consequent as well.
+    pub confidence: f64,
+    /// The confidence over the fraction of all transactions containing
+    /// the consequent, see [`PrefixTree::lift`](struct.PrefixTree.html#method.lift).
+    pub lift: f64,
+}
+
+/// Transactions collected for building a canonical FP-tree, see
+/// [`build`](#method.build).
+#[derive(Debug, Clone, Default)]
+pub struct TransactionDatabase<K> {
+    transactions: Vec<Vec<K>>,
+}
+
+impl<K: Ord + Hash + Clone> TransactionDatabase<K> {
+    pub fn new() -> TransactionDatabase<K> {
+        TransactionDatabase { transactions: Vec::new() }
+    }
+
+    /// Adds a transaction, whose items are treated as a set.
+    pub fn push<Q: AsRef<[K]>>(&mut self, transaction: Q) {
+        self.transactions.push(transaction.as_ref().to_vec());
+    }
+
+    pub fn len(&self) -> usize {
+        self.transactions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.transactions.is_empty()
+    }
+
+    /// Returns the number of transactions containing each item.
+    pub fn item_supports(&self) -> HashMap<K, u32> {
+        count_supports(self.transactions.iter().map(|x| (&x[..], 1)))
+    }
+
+    /// Builds the FP-tree of the transactions, leaving out the items in
+    /// fewer than `min_support` of them.
+    ///
+    /// The first pass counts the support of every item, the second
+    /// appends each transaction through
+    /// [`append_sorted`](struct.PrefixTree.html#method.append_sorted), so
+    /// the tree does not depend on the order of the transactions or of the
+    /// items within them. Transactions left without items are not
+    /// appended.
+    pub fn build(self, min_support: u32) -> PrefixTree<K, u32> {
+        let supports = self.item_supports();
+        let mut tree = PrefixTree::new();
+        for transaction in self.transactions {
+            let items = item_set(transaction.into_iter().filter(|item| supports[item] >= min_support));
+            if !items.is_empty() {
+                tree.append_sorted(&items, &supports);
+            }
+        }
+        tree
+    }
+}
+
+impl<K: Ord + Hash + Clone, Q: AsRef<[K]>> FromIterator<Q> for TransactionDatabase<K> {
+    fn from_iter<I: IntoIterator<Item = Q>>(transactions: I) -> TransactionDatabase<K> {
+        let mut database = TransactionDatabase::new();
+        database.extend(transactions);
+        database
+    }
+}
+
+impl<K: Ord + Hash + Clone, Q: AsRef<[K]>> Extend<Q> for TransactionDatabase<K> {
+    fn extend<I: IntoIterator<Item = Q>>(&mut self, transactions: I) {
+        for transaction in transactions {
+            self.push(transaction);
+        }
+    }
+}
+
+impl<K: Eq + Hash + Clone> PrefixTree<K, u32> {
+    /// Returns, for every item, the summed counts of the nodes whose key
+    /// contains it.
+    ///
+    /// This is the first scan of FP-growth. Each node is counted once per
+    /// item, however often the item repeats within the node's key: `[1, 2,
+    /// 1]` appended twice gives item `1` a support of 2. An item repeated
+    /// across nodes on one path is counted once per node, so supports only
+    /// equal transaction counts when no key repeats an item.
+    pub fn item_supports(&self) -> HashMap<K, u32> {
+        self.nodeindex.keys()
+            .map(|item| (item.clone(), self.item_count(item)))
+            .collect()
+    }
+
+    /// Returns the support of a single item as
+    /// [`item_supports`](#method.item_supports) counts it, or 0 if no key
+    /// contains it. Only the nodes holding `item` are visited.
+    pub fn item_count(&self, item: &K) -> u32 {
+        self.node_chain(item).filter_map(|x| x.value).fold(0, u32::saturating_add)
+    }
+
+    /// Returns the number of appended transactions containing every item
+    /// of `items`, each transaction counted as often as it was appended
+    /// itself.
+    pub fn itemset_support(&self, items: &[K]) -> u32 {
+        self.own_counts().into_iter()
+            .filter(|(key, _)| items.iter().all(|item| key.contains(item)))
+            .map(|(_, n)| n)
+            .fold(0, u32::saturating_add)
+    }
+
+    /// Returns the lift of the rule `antecedent` → `consequent`: how much
+    /// more often the two occur together than if they were independent,
+    /// relative to [`total_inserts`](#method.total_inserts) transactions.
+    ///
+    /// A lift near 1 means the sides are independent, above 1 that they
+    /// occur together more often. `None` if either side is in no
+    /// transaction.
+    pub fn lift(&self, antecedent: &[K], consequent: &[K]) -> Option<f64> {
+        let a = self.itemset_support(antecedent);
+        let b = self.itemset_support(consequent);
+        if a == 0 || b == 0 {
+            return None;
+        }
+        let both = self.itemset_support(&[antecedent, consequent].concat());
+        Some(f64::from(both) * self.total_inserts() as f64 / (f64::from(a) * f64::from(b)))
+    }
+}
+
+impl<K: Ord + Hash + Clone> PrefixTree<K, u32> {
+    /// Appends `items` reordered by descending frequency in `order`, ties
+    /// broken by ascending item. Items missing from `order` count as
+    /// frequency 0.
+    ///
+    /// Unlike `append`, which keeps the caller's order, this builds the
+    /// canonical FP-tree: transactions sharing frequent items share the
+    /// path leading to them, so the tree is usually smaller than one built
+    /// from the same transactions by `append`. Keys are stored in the
+    /// sorted order, so lookups must use that order as well.
+    pub fn append_sorted(&mut self, items: &[K], order: &HashMap<K, u32>) {
+        let mut items = items.to_vec();
+        sort_by_frequency(&mut items, order);
+        self.append(items);
+    }
+
+    /// Returns every itemset contained in at least `min_support` of the
+    /// appended transactions, along with that number.
+    ///
+    /// Each appended key is one transaction, counted as often as it was
+    /// appended itself; its items are treated as a set. Counts stored in
+    /// the tree are prefix counts, so they are not used as supports
+    /// directly. Instead the transactions are reordered by descending item
+    /// support into an FP-tree, which is mined with FP-growth.
+    ///
+    /// Items within an itemset are sorted ascending and itemsets are
+    /// returned in lexicographic order.
+    pub fn frequent_itemsets(&self, min_support: u32) -> Vec<(Vec<K>, u32)> {
+        let transactions = self.own_counts();
+        let supports = count_supports(transactions.iter().map(|&(ref key, n)| (&key[..], n)));
+
+        let mut fp_tree = PrefixTree::new();
+        for (key, n) in transactions {
+            let mut items = item_set(key.into_iter().filter(|item| supports[item] >= min_support));
+            sort_by_frequency(&mut items, &supports);
+            fp_tree.append_with_count(items, n);
+        }
+
+        let mut itemsets = Vec::new();
+        fp_tree.fp_growth(&mut Vec::new(), min_support, &mut itemsets);
+        for &mut (ref mut items, _) in &mut itemsets {
+            items.sort();
+        }
+        itemsets.sort();
+        itemsets
+    }
+
+    /// Returns the frequent itemsets, see
+    /// [`frequent_itemsets`](#method.frequent_itemsets), which no superset
+    /// matches in support. Their supports give the support of every other
+    /// frequent itemset: that of its smallest closed superset.
+    pub fn closed_itemsets(&self, min_support: u32) -> Vec<(Vec<K>, u32)> {
+        let itemsets = self.frequent_itemsets(min_support);
+        itemsets.iter()
+            .filter(|&&(ref items, n)| {
+                !itemsets.iter().any(|&(ref other, m)| m == n && is_proper_subset(items, other))
+            })
+            .cloned()
+            .collect()
+    }
+
+    /// Returns the frequent itemsets, see
+    /// [`frequent_itemsets`](#method.frequent_itemsets), which are not
+    /// contained in any other frequent itemset.
+    pub fn maximal_itemsets(&self, min_support: u32) -> Vec<(Vec<K>, u32)> {
+        let itemsets = self.frequent_itemsets(min_support);
+        itemsets.iter()
+            .filter(|(items, _)| !itemsets.iter().any(|(other, _)| is_proper_subset(items, other)))
+            .cloned()
+            .collect()
+    }
+
+    /// Returns the rules splitting an itemset of at least `min_support`
+    /// transactions, see [`frequent_itemsets`](#method.frequent_itemsets),
+    /// whose confidence is at least `min_confidence`.
+    ///
+    /// The confidence of a rule is the support of the whole itemset over
+    /// the support of its antecedent. Rules are sorted by antecedent, then
+    /// consequent, items ascending within each side.
+    pub fn association_rules(&self, min_support: u32, min_confidence: f64) -> Vec<Rule<K>> {
+        let itemsets = self.frequent_itemsets(min_support);
+        let supports: HashMap<&[K], u32> = itemsets.iter().map(|&(ref items, n)| (&items[..], n)).collect();
+        let total = self.total_inserts() as f64;
+        let mut rules = Vec::new();
+        for &(ref items, support) in &itemsets {
+            for (antecedent, consequent) in splits(items) {
+                // Every subset of a frequent itemset is frequent as well.
+                let confidence = f64::from(support) / f64::from(supports[&antecedent[..]]);
+                if confidence >= min_confidence {
+                    let lift = confidence * total / f64::from(supports[&consequent[..]]);
+                    rules.push(Rule { antecedent, consequent, support, confidence, lift });
+                }
+            }
+        }
+        rules.sort_by(|a, b| (&a.antecedent, &a.consequent).cmp(&(&b.antecedent, &b.consequent)));
+        rules
+    }
+
+    /// Mines this FP-tree, whose paths all follow one item order, for
+    /// itemsets extending `suffix`.
+    fn fp_growth(&self, suffix: &mut Vec<K>, min_support: u32, out: &mut Vec<(Vec<K>, u32)>) {
+        for (item, support) in self.item_supports() {
+            if support < min_support {
+                continue;
+            }
+            suffix.push(item.clone());
+            out.push((suffix.clone(), support));
+            let mut conditional = PrefixTree::new();
+            for (path, n) in self.conditional_pattern_base(&item) {
+                conditional.append_with_count(path, n);
+            }
+            conditional.fp_growth(suffix, min_support, out);
+            suffix.pop();
+        }
+    }
+}
+
+/// Returns whether the sorted `items` are contained in the sorted `other`,
+/// which holds more items.
+fn is_proper_subset<K: Ord>(items: &[K], other: &[K]) -> bool {
+    if items.len() >= other.len() {
+        return false;
+    }
+    let mut rest = other.iter();
+    items.iter().all(|item| rest.any(|x| x == item))
+}
+
+/// Returns every way to split `items` into two non-empty parts, keeping
+/// the order of items within each.
+fn splits<K: Clone>(items: &[K]) -> Vec<(Vec<K>, Vec<K>)> {
+    let mut parts = vec![(Vec::new(), Vec::new())];
+    for item in items {
+        let mut more = Vec::with_capacity(parts.len() * 2);
+        for (left, right) in parts {
+            let mut with_left = left.clone();
+            with_left.push(item.clone());
+            more.push((with_left, right.clone()));
+            let mut with_right = right;
+            with_right.push(item.clone());
+            more.push((left, with_right));
+        }
+        parts = more;
+    }
+    parts.retain(|(left, right)| !left.is_empty() && !right.is_empty());
+    parts
+}
+
+/// Returns the distinct `items` in ascending order.
+fn item_set<K: Ord, I: IntoIterator<Item = K>>(items: I) -> Vec<K> {
+    let mut items: Vec<K> = items.into_iter().collect();
+    items.sort();
+    items.dedup();
+    items
+}
+
+/// Returns the number of transactions containing each item, each
+/// transaction weighed by its count and its items treated as a set.
+/// Supports saturate at `u32::MAX`.
+fn count_supports<'a, K, I>(transactions: I) -> HashMap<K, u32>
+    where K: 'a + Ord + Hash + Clone, I: IntoIterator<Item = (&'a [K], u32)>
+{
+    let mut supports = HashMap::new();
+    for (items, n) in transactions {
+        for item in item_set(items.iter().cloned()) {
+            let support = supports.entry(item).or_insert(0u32);
+            *support = support.saturating_add(n);
+        }
+    }
+    supports
+}
+
+fn sort_by_frequency<K: Ord + Hash>(items: &mut [K], order: &HashMap<K, u32>) {
+    let frequency = |item: &K| order.get(item).cloned().unwrap_or(0);
+    items.sort_by(|a, b| frequency(b).cmp(&frequency(a)).then_with(|| a.cmp(b)));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TransactionDatabase;
+    use prelude::*;
+    use Tree;
+
+    fn transactions() -> Vec<Vec<u32>> {
+        vec![
+            vec![8, 5, 1, 3], vec![6, 2, 4, 7], vec![8, 6, 2, 5, 4, 1],
+            vec![2, 8, 4, 0, 7], vec![8, 6, 2, 0], vec![6, 8, 4, 1],
+            vec![8, 5, 0], vec![8, 6, 5, 0, 3], vec![8, 2], vec![1, 7],
+        ]
+    }
+
+    /// Counts the support of every itemset by enumerating the subsets of
+    /// each transaction.
+    fn brute_force(transactions: &[Vec<u32>], min_support: u32) -> Vec<(Vec<u32>, u32)> {
+        let mut supports: HashMap<Vec<u32>, u32> = HashMap::new();
+        for t in transactions {
+            let mut items = t.clone();
+            items.sort();
+            items.dedup();
+            for mask in 1..(1u32 << items.len()) {
+                let subset: Vec<u32> = (0..items.len())
+                    .filter(|i| mask & (1 << i) != 0)
+                    .map(|i| items[i])
+                    .collect();
+                *supports.entry(subset).or_insert(0) += 1;
+            }
+        }
+        let mut itemsets: Vec<_> = supports.into_iter().filter(|&(_, n)| n >= min_support).collect();
+        itemsets.sort();
+        itemsets
+    }
+
+    #[test]
+    fn test_item_supports() {
+        let t: Tree = transactions().into_iter().collect();
+        let expected: HashMap<u32, u32> = vec![
+            (8, 8), (6, 5), (2, 5), (5, 4), (4, 4), (1, 4), (0, 4), (7, 3), (3, 2),
+        ].into_iter().collect();
+        assert_eq!(t.item_supports(), expected);
+        assert!(Tree::new().item_supports().is_empty());
+    }
+
+    #[test]
+    fn test_item_supports_repeated_items() {
+        let mut t = Tree::new();
+        t.append_with_count(vec![1, 2, 1], 2);
+        assert_eq!(t.item_supports()[&1], 2);
+
+        // once the key is split, each node holding the item counts
+        t.append(vec![1]);
+        assert_eq!(t.item_supports()[&1], 5);
+        assert_eq!(t.item_supports()[&2], 2);
+    }
+
+    #[test]
+    fn test_append_sorted() {
+        let raw: Tree = transactions().into_iter().collect();
+        let order = raw.item_supports();
+        let mut t = Tree::new();
+        for items in transactions() {
+            t.append_sorted(&items, &order);
+        }
+        assert_eq!(t.total_inserts(), raw.total_inserts());
+        assert_eq!(t.node_count(), 13);
+        assert!(t.node_count() < raw.node_count());
+
+        let root = t.root.children().next().unwrap();
+        assert_eq!(root.key, vec![8]);
+        assert_eq!(root.value, Some(8));
+        // 2 and 6 are both in 5 transactions; 2 sorts first
+        assert_eq!(t.get_count([8, 2]), Some(4));
+        assert!(t.contains([8, 2, 6, 1, 4, 5]));
+        assert!(!t.contains([8, 6, 2, 5, 4, 1]));
+        // 9 is unknown to the order and goes last
+        t.append_sorted(&[9, 3, 8], &order);
+        assert!(t.contains([8, 3, 9]));
+    }
+
+    #[test]
+    fn test_frequent_itemsets() {
+        let t: Tree = transactions().into_iter().collect();
+        assert_eq!(t.frequent_itemsets(4), vec![
+            (vec![0], 4), (vec![0, 8], 4), (vec![1], 4), (vec![2], 5), (vec![2, 8], 4),
+            (vec![4], 4), (vec![5], 4), (vec![5, 8], 4), (vec![6], 5), (vec![6, 8], 4),
+            (vec![8], 8),
+        ]);
+        for min_support in 1..5 {
+            assert_eq!(t.frequent_itemsets(min_support), brute_force(&transactions(), min_support));
+        }
+        assert!(t.frequent_itemsets(9).is_empty());
+        assert!(Tree::new().frequent_itemsets(1).is_empty());
+    }
+
+    #[test]
+    fn test_frequent_itemsets_repeated_transactions() {
+        let mut t = Tree::new();
+        t.append_with_count(vec![1, 2], 3);
+        t.append(vec![2, 1, 3]);
+        t.append(vec![1]);
+        assert_eq!(t.frequent_itemsets(4), vec![(vec![1], 5), (vec![1, 2], 4), (vec![2], 4)]);
+    }
+
+    #[test]
+    fn test_frequent_itemsets_saturate() {
+        let mut t = Tree::new();
+        t.append_with_count(vec![1], u32::MAX);
+        t.append_with_count(vec![2, 1], 5);
+        assert_eq!(t.frequent_itemsets(1), vec![(vec![1], u32::MAX), (vec![1, 2], 5), (vec![2], 5)]);
+    }
+
+    #[test]
+    fn test_association_rules() {
+        let t: Tree = transactions().into_iter().collect();
+        let rules = t.association_rules(4, 0.75);
+        let summary: Vec<_> = rules.iter()
+            .map(|r| (r.antecedent.clone(), r.consequent.clone(), r.support, r.confidence))
+            .collect();
+        assert_eq!(summary, vec![
+            (vec![0], vec![8], 4, 1.0), (vec![2], vec![8], 4, 0.8),
+            (vec![5], vec![8], 4, 1.0), (vec![6], vec![8], 4, 0.8),
+        ]);
+
+        let supports: HashMap<_, _> = brute_force(&transactions(), 1).into_iter().collect();
+        let rules = t.association_rules(2, 0.0);
+        assert!(rules.iter().any(|r| r.antecedent == vec![0, 5] && r.consequent == vec![8]));
+        for r in &rules {
+            let mut items = [&r.antecedent[..], &r.consequent[..]].concat();
+            items.sort();
+            assert_eq!(r.support, supports[&items]);
+            assert_eq!(r.confidence, f64::from(r.support) / f64::from(supports[&r.antecedent]));
+            assert!((r.lift - t.lift(&r.antecedent, &r.consequent).unwrap()).abs() < 1e-12);
+        }
+        assert!(t.association_rules(9, 0.0).is_empty());
+    }
+
+    #[test]
+    fn test_closed_maximal_itemsets() {
+        let t: Tree = transactions().into_iter().collect();
+        assert_eq!(t.closed_itemsets(4), vec![
+            (vec![0, 8], 4), (vec![1], 4), (vec![2], 5), (vec![2, 8], 4), (vec![4], 4),
+            (vec![5, 8], 4), (vec![6], 5), (vec![6, 8], 4), (vec![8], 8),
+        ]);
+        assert_eq!(t.maximal_itemsets(4), vec![
+            (vec![0, 8], 4), (vec![1], 4), (vec![2, 8], 4), (vec![4], 4), (vec![5, 8], 4), (vec![6, 8], 4),
+        ]);
+        for min_support in 1..5 {
+            let frequent = t.frequent_itemsets(min_support);
+            let closed = t.closed_itemsets(min_support);
+            let maximal = t.maximal_itemsets(min_support);
+            assert!(closed.iter().all(|x| frequent.contains(x)));
+            assert!(maximal.iter().all(|x| closed.contains(x)));
+        }
+        assert!(Tree::new().closed_itemsets(1).is_empty());
+    }
+
+    #[test]
+    fn test_transaction_database() {
+        let raw: Tree = transactions().into_iter().collect();
+        let database: TransactionDatabase<u32> = transactions().into_iter().collect();
+        assert_eq!(database.len(), 10);
+        assert_eq!(database.item_supports(), raw.item_supports());
+        let t = database.clone().build(3);
+        assert_eq!(t.validate(), Ok(()));
+        assert_eq!(t.total_inserts(), 10);
+        assert!(t.contains([8, 1, 5]));
+        assert!(t.contains([2, 6, 4, 7]));
+        assert!(t.node_chain(&3).next().is_none());
+        assert_eq!(t.frequent_itemsets(3), raw.frequent_itemsets(3));
+
+        let mut shuffled = TransactionDatabase::new();
+        for mut items in transactions().into_iter().rev() {
+            items.reverse();
+            shuffled.push(items);
+        }
+        shuffled.push([9, 9]);
+        assert_eq!(shuffled.build(3), t);
+        assert_eq!(t.item_count(&8), 8);
+        assert_eq!(t.item_count(&7), 3);
+        assert_eq!(t.item_count(&3), 0);
+        assert_eq!(database.build(9), Tree::new());
+        assert!(TransactionDatabase::<u32>::new().is_empty());
+    }
+
+    #[test]
+    fn test_lift() {
+        let t: Tree = transactions().into_iter().collect();
+        assert_eq!(t.itemset_support(&[8, 0]), 4);
+        assert_eq!(t.itemset_support(&[]), 10);
+        // 0 → 8: confidence 1, 8 in 8 of 10 transactions
+        assert_eq!(t.lift(&[0], &[8]), Some(1.25));
+        assert_eq!(t.lift(&[8], &[0]), Some(1.25));
+        // 7 is in 3 transactions and 8 in 8, both together in 1
+        assert_eq!(t.lift(&[7], &[8]), Some(10.0 / 24.0));
+        assert_eq!(t.lift(&[9], &[8]), None);
+        assert_eq!(t.lift(&[8], &[9]), None);
+        assert_eq!(Tree::new().lift(&[1], &[2]), None);
+    }
+}
@@ -0,0 +1,30 @@
+//! The parts of `std` the crate uses, taken from `core`, `alloc` and
+//! `hashbrown` instead when built with the `no_std` feature.
+
+#[cfg(not(feature = "no_std"))]
+pub use std::{cmp, convert, fmt, hash, iter, mem, ops, slice, str};
+#[cfg(not(feature = "no_std"))]
+pub use std::borrow::ToOwned;
+#[cfg(not(feature = "no_std"))]
+pub use std::collections::{BTreeMap, BinaryHeap, HashMap, HashSet, VecDeque};
+#[cfg(not(feature = "no_std"))]
+pub use std::string::{String, ToString};
+#[cfg(not(feature = "no_std"))]
+pub use std::sync::Arc;
+#[cfg(not(feature = "no_std"))]
+pub use std::vec::Vec;
+
+#[cfg(feature = "no_std")]
+pub use core::{cmp, convert, fmt, hash, iter, mem, ops, slice, str};
+#[cfg(feature = "no_std")]
+pub use alloc::borrow::ToOwned;
+#[cfg(feature = "no_std")]
+pub use alloc::collections::{BTreeMap, BinaryHeap, VecDeque};
+#[cfg(feature = "no_std")]
+pub use alloc::string::{String, ToString};
+#[cfg(feature = "no_std")]
+pub use alloc::sync::Arc;
+#[cfg(feature = "no_std")]
+pub use alloc::vec::Vec;
+#[cfg(feature = "no_std")]
+pub use hashbrown::{HashMap, HashSet};
@@ -0,0 +1,66 @@
+//! Serde support, enabled by the `serde` feature.
+//!
+//! A counting tree is stored as the list of its appended keys, each with
+//! how often it was appended itself. Nothing of the node layout is stored;
+//! deserializing appends the keys to a fresh tree.
+
+use prelude::*;
+use prelude::hash::Hash;
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use super::PrefixTree;
+
+impl<K: Eq + Hash + Clone + Serialize> Serialize for PrefixTree<K, u32> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.own_counts())
+    }
+}
+
+impl<'de, K: Eq + Hash + Clone + Deserialize<'de>> Deserialize<'de> for PrefixTree<K, u32> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let keys = Vec::<(Vec<K>, u32)>::deserialize(deserializer)?;
+        Ok(PrefixTree::from_own_counts(keys))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate serde_json;
+
+    use prelude::*;
+    use Tree;
+
+    #[test]
+    fn test_json_round_trip() {
+        let mut t = Tree::new();
+        t.append_all(vec![
+            vec![8, 5, 1, 3], vec![6, 2, 4, 7], vec![8, 6, 2, 5, 4, 1],
+            vec![2, 8, 4, 0, 7], vec![8, 6, 2, 0], vec![6, 8, 4, 1],
+            vec![8, 5, 0], vec![8, 6, 5, 0, 3], vec![8, 2], vec![1, 7],
+        ]);
+        t.append(vec![8, 6]);
+        let json = serde_json::to_string(&t).unwrap();
+        let u: Tree = serde_json::from_str(&json).unwrap();
+        assert_eq!(u.iter().collect::<Vec<_>>(), t.iter().collect::<Vec<_>>());
+        assert_eq!(u.total_inserts(), t.total_inserts());
+        assert_eq!(u.distinct_keys(), t.distinct_keys());
+
+        // keys whose own appends were all taken off are kept
+        t.decrement([8, 6]);
+        t.decrement([8, 5, 0]);
+        let u: Tree = serde_json::from_str(&serde_json::to_string(&t).unwrap()).unwrap();
+        assert!(u.contains([8, 6]));
+        assert_eq!(u.get_count([8, 5, 0]), Some(0));
+        assert_eq!(u.iter_sorted().collect::<Vec<_>>(), t.iter_sorted().collect::<Vec<_>>());
+        assert_eq!(u.validate(), Ok(()));
+    }
+
+    #[test]
+    fn test_json_format() {
+        let t: Tree = vec![vec![3u32, 137u32], vec![3u32, 137u32, 137u32]].into_iter().collect();
+        assert_eq!(serde_json::to_string(&t).unwrap(), "[[[3,137],1],[[3,137,137],1]]");
+        let u: Tree = serde_json::from_str("[]").unwrap();
+        assert!(u.is_empty());
+    }
+}
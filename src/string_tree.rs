@@ -0,0 +1,155 @@
+use prelude::*;
+
+use super::{Interner, Tree};
+
+/// How a `StringTree` splits its input into tokens.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Tokenizer {
+    /// Every `char` is a token.
+    Chars,
+    /// Tokens are separated by whitespace.
+    Whitespace,
+}
+
+/// A counting tree over strings.
+///
+/// Tokens are interned to `u32` ids on insertion by an [`Interner`] and the
+/// ids are stored in a plain [`Tree`], so a string behaves exactly like the
+/// id sequence it maps to.
+#[derive(Debug)]
+pub struct StringTree {
+    tree: Tree,
+    tokenizer: Tokenizer,
+    names: Interner,
+}
+
+impl StringTree {
+    pub fn new(tokenizer: Tokenizer) -> StringTree {
+        StringTree {
+            tree: Tree::new(),
+            tokenizer,
+            names: Interner::new(),
+        }
+    }
+
+    fn tokens<'a>(&self, s: &'a str) -> Vec<&'a str> {
+        match self.tokenizer {
+            Tokenizer::Chars => s.char_indices()
+                .map(|(i, c)| &s[i..i + c.len_utf8()])
+                .collect(),
+            Tokenizer::Whitespace => s.split_whitespace().collect(),
+        }
+    }
+
+    /// Appends `s` to the tree. Strings without any token are ignored.
+    pub fn append(&mut self, s: &str) {
+        let tokens = self.tokens(s);
+        if tokens.is_empty() {
+            return;
+        }
+        let key = self.names.intern_all(tokens);
+        self.tree.append(key);
+    }
+
+    /// Returns how often `s` was appended, counting the strings it is a
+    /// prefix of, or `None` unless `s` itself was appended, as
+    /// [`PrefixTree::get_count`](struct.PrefixTree.html#method.get_count)
+    /// does.
+    pub fn find(&self, s: &str) -> Option<u32> {
+        let key = self.key(s)?;
+        self.tree.get_count(key)
+    }
+
+    /// Returns how many appended strings start with the tokens of `s`,
+    /// each counted as often as it was appended, or `None` if none does.
+    /// Unlike [`find`](#method.find) `s` need not have been appended.
+    pub fn prefix_count(&self, s: &str) -> Option<u32> {
+        let key = self.key(s)?;
+        self.tree.root.find_prefix(&key, &mut Vec::new()).and_then(|n| n.value)
+    }
+
+    /// Returns the ids of the tokens of `s`, or `None` if it has no tokens
+    /// or one was never interned.
+    fn key(&self, s: &str) -> Option<Vec<u32>> {
+        let tokens = self.tokens(s);
+        if tokens.is_empty() {
+            return None;
+        }
+        self.names.ids(tokens)
+    }
+
+    /// Returns the token interned as `id`.
+    pub fn symbol(&self, id: u32) -> Option<&str> {
+        self.names.resolve(id)
+    }
+
+    /// Returns the id `token` was interned as.
+    pub fn id(&self, token: &str) -> Option<u32> {
+        self.names.id(token)
+    }
+
+    pub fn tree(&self) -> &Tree {
+        &self.tree
+    }
+
+    pub fn interner(&self) -> &Interner {
+        &self.names
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{StringTree, Tokenizer};
+
+    #[test]
+    fn test_words() {
+        let mut t = StringTree::new(Tokenizer::Whitespace);
+        t.append("foo bar");
+        t.append("foo bar baz");
+        t.append("foo  qux");
+        assert_eq!(t.find("foo"), None);
+        assert_eq!(t.find("foo bar"), Some(2));
+        assert_eq!(t.find("foo bar baz"), Some(1));
+        assert_eq!(t.find("foo baz"), None);
+        assert_eq!(t.find("nope"), None);
+        assert_eq!(t.prefix_count("foo"), Some(3));
+        assert_eq!(t.prefix_count("foo bar"), Some(2));
+        assert_eq!(t.prefix_count("foo baz"), None);
+    }
+
+    #[test]
+    fn test_chars() {
+        let mut t = StringTree::new(Tokenizer::Chars);
+        t.append("hello");
+        t.append("help");
+        assert_eq!(t.find("hel"), None);
+        assert_eq!(t.find("he"), None);
+        assert_eq!(t.find("help"), Some(1));
+        assert_eq!(t.prefix_count("hel"), Some(2));
+        assert_eq!(t.prefix_count("he"), Some(2));
+        assert_eq!(t.prefix_count("hell"), Some(1));
+        assert_eq!(t.prefix_count("hex"), None);
+        assert_eq!(t.symbol(t.id("h").unwrap()), Some("h"));
+        assert_eq!(t.symbol(99), None);
+    }
+
+    #[test]
+    fn test_empty() {
+        let mut t = StringTree::new(Tokenizer::Whitespace);
+        t.append("");
+        t.append("   ");
+        assert!(t.tree().find([]).is_none());
+        assert_eq!(t.find(""), None);
+        assert_eq!(t.prefix_count(""), None);
+    }
+
+    #[test]
+    fn test_repeated_tokens() {
+        let mut t = StringTree::new(Tokenizer::Whitespace);
+        t.append("a a b");
+        let a = t.id("a").unwrap();
+        let b = t.id("b").unwrap();
+        assert_eq!(t.tree().find(vec![a, a, b]).unwrap().value, t.find("a a b"));
+        assert_eq!(t.find("a a b"), Some(1));
+    }
+}
@@ -0,0 +1,59 @@
+//! Counts the allocations appends make. The counting allocator replaces
+//! the global one for the whole test binary, so it lives apart from the
+//! unit tests.
+
+extern crate prefix_tree;
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+
+use prefix_tree::Tree;
+
+/// Counts allocations per thread, so tests running in parallel do not
+/// see each other's.
+struct CountingAllocator;
+
+thread_local! {
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let _ = ALLOCATIONS.try_with(|n| n.set(n.get() + 1));
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+fn count_allocations<F: FnOnce()>(f: F) -> usize {
+    let before = ALLOCATIONS.with(|n| n.get());
+    f();
+    ALLOCATIONS.with(|n| n.get()) - before
+}
+
+#[test]
+fn test_append_allocations() {
+    // every key takes 10 levels, one per bit
+    let keys: Vec<Vec<u32>> = (0..1024u32)
+        .map(|i| (0..10).map(|bit| (i >> bit & 1) + 2 * bit).collect())
+        .collect();
+    let mut t = Tree::new();
+    let new_keys = count_allocations(|| for key in &keys {
+        t.append(key);
+    });
+    assert!(new_keys < 8 * keys.len());
+    assert_eq!(t.validate(), Ok(()));
+
+    let known_keys = count_allocations(|| for key in &keys {
+        t.append(key);
+    });
+    assert_eq!(known_keys, 0);
+    assert_eq!(t.get_count(&keys[5]), Some(2));
+    assert_eq!(t.validate(), Ok(()));
+}